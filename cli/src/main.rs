@@ -108,7 +108,7 @@ fn main() {
 
 	let image = tim2::load("./assets/test.tm2").unwrap();
 	let frame = image.get_frame(0);
-	let pixels = frame.to_raw(None);
+	let pixels = frame.to_raw(None).unwrap();
 	let texture = Texture::make(&pixels, frame.width(), frame.height(), false).unwrap();
 
 	window.set_size(frame.width() as i32, frame.height() as i32);
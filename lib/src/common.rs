@@ -1,9 +1,17 @@
-pub fn get_slice<'a>(buffer: &'a [u8], offset: &mut usize, size: usize) -> &'a [u8] {
+use crate::error::Error;
+
+pub fn get_slice<'a>(buffer: &'a [u8], offset: &mut usize, size: usize) -> Result<&'a [u8], Error> {
 	let start_index = *offset;
-	let end_index = start_index + size;
+	let slice = start_index.checked_add(size)
+		.and_then(|end_index| buffer.get(start_index..end_index))
+		.ok_or(Error::UnexpectedEof { offset: start_index, needed: size })?;
 
 	*offset += size;
-	&buffer[start_index..end_index]
+	Ok(slice)
+}
+
+pub fn align(offset: usize, alignment: usize) -> usize {
+	offset.div_ceil(alignment) * alignment
 }
 
 pub fn clone_into_array<A, T>(slice: &[T]) -> A
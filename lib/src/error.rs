@@ -1,13 +1,95 @@
+use std::error;
+use std::fmt;
 use std::io;
 
+/// Errors raised while reading, decoding or writing TIM2 data. Failures
+/// inside a picture are wrapped in `Picture`, which gives its index and the
+/// file offset that parsing reached.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::Error;
+///
+/// let buffer = std::fs::read("../assets/test.tm2").unwrap();
+///
+/// match tim2::from_buffer(&buffer[..1000]) {
+///     Err(Error::Picture { index, offset, source }) => println!("picture {} @{:#x}: {}", index, offset, source),
+///     Err(err) => println!("{}", err),
+///     Ok(_) => {},
+/// }
+/// ```
 #[derive(Debug)]
 pub enum Error {
+	BufferTooSmall { needed: usize, found: usize },
+	DataKindMismatch,
+	FrameNotFound(usize),
 	InvalidIdentifier(u32),
+	InvalidAlignment(u8),
 	InvalidBpp(u8),
 	InvalidBppFormat(u8),
+	InvalidHeaderSize(usize),
+	InvalidPaletteSize(usize),
 	InvalidRange(usize),
+	InvalidRegion { x: usize, y: usize, width: usize, height: usize },
+	InvalidStride(usize),
 	Io(io::Error),
+	LimitExceeded { name: &'static str, value: usize, limit: usize },
+	MipmapNotFound(usize),
+	MipmapSizeMismatch { image_size: usize, levels: usize },
+	PaletteIndexOutOfRange(usize),
+	PaletteNotFound(usize),
+	Picture { index: usize, offset: usize, source: Box<Error> },
 	TrueColorAndPaletteFound,
+	UnexpectedEof { offset: usize, needed: usize },
+	UserDataTooLarge { size: usize, space: usize },
+}
+
+impl Error {
+	/// Wraps the error with the index of the picture it came from and the file
+	/// offset parsing reached.
+	pub(crate) fn in_picture(self, index: usize, offset: usize) -> Error {
+		Error::Picture { index, offset, source: Box::new(self) }
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::BufferTooSmall { needed, found } => write!(f, "buffer too small: {} bytes needed, {} found", needed, found),
+			Error::DataKindMismatch => write!(f, "pixel data does not match the picture's format"),
+			Error::FrameNotFound(n) => write!(f, "picture {} not found", n),
+			Error::InvalidIdentifier(n) => write!(f, "invalid identifier {:#010x}, expected TIM2", n),
+			Error::InvalidAlignment(n) => write!(f, "invalid alignment id {}", n),
+			Error::InvalidBpp(n) => write!(f, "unsupported bpp {}", n),
+			Error::InvalidBppFormat(n) => write!(f, "unsupported bpp format {}", n),
+			Error::InvalidHeaderSize(n) => write!(f, "invalid picture header size {}", n),
+			Error::InvalidPaletteSize(n) => write!(f, "invalid palette size {}", n),
+			Error::InvalidRange(n) => write!(f, "index or size out of range: {}", n),
+			Error::InvalidRegion { x, y, width, height } => write!(f, "region {}x{} at ({}, {}) is outside the picture", width, height, x, y),
+			Error::InvalidStride(n) => write!(f, "row stride {} is shorter than a row", n),
+			Error::Io(err) => write!(f, "i/o error: {}", err),
+			Error::LimitExceeded { name, value, limit } => write!(f, "{} {} exceeds the limit of {}", name, value, limit),
+			Error::MipmapNotFound(n) => write!(f, "mipmap level {} not found", n),
+			Error::MipmapSizeMismatch { image_size, levels } => write!(f, "mipmap levels add up to {} bytes, but image size is {}", levels, image_size),
+			Error::PaletteIndexOutOfRange(n) => write!(f, "palette index {} out of range", n),
+			Error::PaletteNotFound(n) => write!(f, "palette {} not found", n),
+			Error::Picture { index, offset, source } => write!(f, "picture {} @{:#x}: {}", index, offset, source),
+			Error::TrueColorAndPaletteFound => write!(f, "true color picture has a palette"),
+			Error::UnexpectedEof { offset, needed } => write!(f, "unexpected end of data at {:#x}, {} bytes needed", offset, needed),
+			Error::UserDataTooLarge { size, space } => write!(f, "user data of {} bytes does not fit the {} byte user area", size, space),
+		}
+	}
+}
+
+impl error::Error for Error {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Error::Io(err) => Some(err),
+			Error::Picture { source, .. } => Some(source.as_ref()),
+			_ => None,
+		}
+	}
 }
 
 impl From<io::Error> for Error {
@@ -1,15 +1,20 @@
 use crate::common::*;
 use crate::error::Error;
-use crate::pixel::{Format, Pixel};
+use crate::gs::{ClutStorageMode, GsTex0, GsTex1, GsTexClut, GsTexa, Psm};
+use crate::options::DecodeOptions;
+use crate::pixel::{AlphaMode, Format, Pixel, PixelLayout};
+use crate::swizzle::{self, SwizzleMap};
+use crate::user_data::UserData;
 
-use byteorder::{ByteOrder, LittleEndian};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use std::borrow::Cow;
+use std::io::Write;
 
-const SWIZZLE_WIDTH: usize = 16;
-const SWIZZLE_HEIGHT: usize = 8;
+pub(crate) const HEADER_SIZE: usize = 48;
 
 pub type PixelBuffer = Vec::<Pixel>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DataKind {
 	Indices(Vec::<u8>),
 	Pixels(PixelBuffer),
@@ -24,8 +29,95 @@ impl DataKind {
 	}
 }
 
-#[derive(Debug)]
-struct Header {
+/// Order of the two texels packed into each byte of a 4bpp image. The GS
+/// reads the low nibble first, but some tools write the high nibble first.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::NibbleOrder;
+///
+/// let mut image = tim2::load("../assets/test.tm2").unwrap();
+/// image.set_nibble_order(NibbleOrder::HighFirst);
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum NibbleOrder {
+	#[default]
+	LowFirst,
+	HighFirst,
+}
+
+impl NibbleOrder {
+	fn unpack(&self, value: u8) -> [u8; 2] {
+		match self {
+			NibbleOrder::LowFirst => [value & 0xF, value >> 4],
+			NibbleOrder::HighFirst => [value >> 4, value & 0xF],
+		}
+	}
+
+	fn pack(&self, first: u8, second: u8) -> u8 {
+		match self {
+			NibbleOrder::LowFirst => (first & 0xF) | (second << 4),
+			NibbleOrder::HighFirst => (first << 4) | (second & 0xF),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MipmapHeader {
+	gs_miptbp_1: u64,
+	gs_miptbp_2: u64,
+	sizes: Vec::<u32>,
+}
+
+impl MipmapHeader {
+	fn read(buffer: &[u8], offset: &mut usize, count: usize) -> Result<MipmapHeader, Error> {
+		let mut load_part = |size| { get_slice(buffer, offset, size) };
+		let gs_miptbp_1 = LittleEndian::read_u64(load_part(8)?);
+		let gs_miptbp_2 = LittleEndian::read_u64(load_part(8)?);
+		let mut sizes = Vec::with_capacity(count);
+
+		for _ in 0..count {
+			sizes.push(LittleEndian::read_u32(load_part(4)?));
+		}
+
+		load_part(MipmapHeader::size(count) - 16 - count * 4)?;
+		Ok(MipmapHeader { gs_miptbp_1, gs_miptbp_2, sizes })
+	}
+
+	fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+		let count = self.sizes.len();
+
+		writer.write_u64::<LittleEndian>(self.gs_miptbp_1)?;
+		writer.write_u64::<LittleEndian>(self.gs_miptbp_2)?;
+		for size in &self.sizes {
+			writer.write_u32::<LittleEndian>(*size)?;
+		}
+
+		writer.write_all(&vec![0u8; MipmapHeader::size(count) - 16 - count * 4])?;
+		Ok(())
+	}
+
+	fn size(count: usize) -> usize {
+		/* the size table is padded out to a 16 byte boundary */
+		(16 + count * 4 + 15) & !15
+	}
+
+	pub fn gs_miptbp_1(&self) -> u64 {
+		self.gs_miptbp_1
+	}
+
+	pub fn gs_miptbp_2(&self) -> u64 {
+		self.gs_miptbp_2
+	}
+
+	pub fn sizes(&self) -> &[u32] {
+		&self.sizes
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameInfo {
 	total_size: u32,
     palette_size: u32,
     image_size: u32,
@@ -41,44 +133,107 @@ struct Header {
 	gs_tex_clut: u32,
 	gs_tex_0: u64,
 	gs_tex_1: u64,
-	user_data: Vec::<u8>,
+	mipmap_header: Option<MipmapHeader>,
+	user_data: UserData,
 }
 
-impl Header {
-	pub fn read(buffer: &[u8], offset: &mut usize) -> Result<Header, Error> {
-		let mut load_part = |size| { get_slice(&buffer, offset, size) };
-
-		let mut result = Header {
-			total_size: LittleEndian::read_u32(load_part(4)),
-			palette_size: LittleEndian::read_u32(load_part(4)),
-			image_size: LittleEndian::read_u32(load_part(4)),
-			header_size: LittleEndian::read_u16(load_part(2)),
-			color_entry_count: LittleEndian::read_u16(load_part(2)),
-			paletted: load_part(1)[0],
-			mipmap_count: load_part(1)[0],
-			clut_format: load_part(1)[0],
-			bpp: Header::find_bpp(load_part(1)[0])?,
-			width: LittleEndian::read_u16(load_part(2)) as usize,
-			height: LittleEndian::read_u16(load_part(2)) as usize,
-			gs_tex_0: LittleEndian::read_u64(load_part(8)),
-			gs_tex_1: LittleEndian::read_u64(load_part(8)),
-			gs_regs: LittleEndian::read_u32(load_part(4)),
-			gs_tex_clut: LittleEndian::read_u32(load_part(4)),
-			user_data: Vec::new(),
+impl FrameInfo {
+	pub fn read(buffer: &[u8], offset: &mut usize) -> Result<FrameInfo, Error> {
+		let mut load_part = |size| { get_slice(buffer, offset, size) };
+
+		let mut result = FrameInfo {
+			total_size: LittleEndian::read_u32(load_part(4)?),
+			palette_size: LittleEndian::read_u32(load_part(4)?),
+			image_size: LittleEndian::read_u32(load_part(4)?),
+			header_size: LittleEndian::read_u16(load_part(2)?),
+			color_entry_count: LittleEndian::read_u16(load_part(2)?),
+			paletted: load_part(1)?[0],
+			mipmap_count: load_part(1)?[0],
+			clut_format: load_part(1)?[0],
+			bpp: FrameInfo::find_bpp(load_part(1)?[0])?,
+			width: LittleEndian::read_u16(load_part(2)?) as usize,
+			height: LittleEndian::read_u16(load_part(2)?) as usize,
+			gs_tex_0: LittleEndian::read_u64(load_part(8)?),
+			gs_tex_1: LittleEndian::read_u64(load_part(8)?),
+			gs_regs: LittleEndian::read_u32(load_part(4)?),
+			gs_tex_clut: LittleEndian::read_u32(load_part(4)?),
+			mipmap_header: None,
+			user_data: UserData::default(),
 		};
 
-		let user_data_size = result.header_size as usize - 48;
+		let mut user_data_size = (result.header_size as usize)
+			.checked_sub(HEADER_SIZE)
+			.ok_or(Error::InvalidHeaderSize(result.header_size as usize))?;
+
+		if result.mipmap_count > 1 {
+			let count = result.mipmap_count as usize;
+
+			user_data_size = user_data_size
+				.checked_sub(MipmapHeader::size(count))
+				.ok_or(Error::InvalidHeaderSize(result.header_size as usize))?;
+
+			let mipmap_header = MipmapHeader::read(buffer, offset, count)?;
+			let levels = mipmap_header.sizes.iter().map(|v| *v as usize).sum();
+
+			/* the data is read level by level, so the CLUT would land wherever the level sizes end */
+			if levels != result.image_size as usize {
+				return Err(Error::MipmapSizeMismatch { image_size: result.image_size as usize, levels });
+			}
+
+			result.mipmap_header = Some(mipmap_header);
+		}
+
 		if user_data_size > 0 {
-			result.user_data = load_part(user_data_size).to_vec();
+			result.user_data = UserData::read(get_slice(buffer, offset, user_data_size)?);
 		}
 
-		if result.palette_size > 0 && result.bpp > 8 {
+		let indexed = result.tex0().psm().is_some_and(|v| v.is_indexed() && v.bpp() == result.bpp);
+
+		if result.palette_size > 0 && result.bpp > 8 && !indexed {
 			Err(Error::TrueColorAndPaletteFound)
+		} else if result.palette_size == 0 && result.bpp <= 8 {
+			Err(Error::InvalidPaletteSize(0))
 		} else {
 			Ok(result)
 		}
 	}
 
+	pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+		writer.write_u32::<LittleEndian>(self.total_size)?;
+		writer.write_u32::<LittleEndian>(self.palette_size)?;
+		writer.write_u32::<LittleEndian>(self.image_size)?;
+		writer.write_u16::<LittleEndian>(self.header_size)?;
+		writer.write_u16::<LittleEndian>(self.color_entry_count)?;
+		writer.write_u8(self.paletted)?;
+		writer.write_u8(self.mipmap_count)?;
+		writer.write_u8(self.clut_format)?;
+		writer.write_u8(FrameInfo::find_bpp_format(self.bpp)?)?;
+		writer.write_u16::<LittleEndian>(self.width as u16)?;
+		writer.write_u16::<LittleEndian>(self.height as u16)?;
+		writer.write_u64::<LittleEndian>(self.gs_tex_0)?;
+		writer.write_u64::<LittleEndian>(self.gs_tex_1)?;
+		writer.write_u32::<LittleEndian>(self.gs_regs)?;
+		writer.write_u32::<LittleEndian>(self.gs_tex_clut)?;
+		if let Some(mipmap_header) = &self.mipmap_header {
+			mipmap_header.write(writer)?;
+		}
+
+		let mut user_data = Vec::new();
+		let user_data_size = (self.header_size as usize)
+			.saturating_sub(HEADER_SIZE)
+			.saturating_sub(self.mipmap_header.as_ref().map_or(0, |v| MipmapHeader::size(v.sizes.len())));
+
+		self.user_data.write(&mut user_data)?;
+		if user_data.len() > user_data_size {
+			return Err(Error::UserDataTooLarge { size: user_data.len(), space: user_data_size });
+		}
+
+		user_data.resize(user_data_size, 0);
+		writer.write_all(&user_data)?;
+
+		Ok(())
+	}
+
 	fn find_bpp(v: u8) -> Result<u8, Error> {
 		match v {
 			1 => Ok(16),
@@ -90,6 +245,124 @@ impl Header {
 		}
 	}
 
+	fn find_bpp_format(bpp: u8) -> Result<u8, Error> {
+		match bpp {
+			16 => Ok(1),
+			24 => Ok(2),
+			32 => Ok(3),
+			4 => Ok(4),
+			8 => Ok(5),
+			n => Err(Error::InvalidBpp(n)),
+		}
+	}
+
+	pub fn level_count(&self) -> usize {
+		match &self.mipmap_header {
+			Some(v) => v.sizes.len(),
+			None => 1,
+		}
+	}
+
+	pub fn level_size(&self, level: usize) -> usize {
+		match &self.mipmap_header {
+			Some(v) => v.sizes[level] as usize,
+			None => self.image_size as usize,
+		}
+	}
+
+	/// Returns the size of the image data and palettes that follow the header.
+	pub fn data_size(&self) -> usize {
+		let levels: usize = (0..self.level_count()).map(|v| self.level_size(v)).sum();
+
+		levels + self.palette_size as usize
+	}
+
+	/// Returns how many CLUTs are stored, each `color_entry_count` colors long.
+	pub fn clut_count(&self) -> usize {
+		let size = self.color_entry_count as usize * self.color_size();
+
+		(self.palette_size as usize).checked_div(size).unwrap_or(0)
+	}
+
+	pub fn level_dimensions(&self, level: usize) -> (usize, usize) {
+		let level = level as u32;
+		let width = self.width.checked_shr(level).unwrap_or(0).max(1);
+		let height = self.height.checked_shr(level).unwrap_or(0).max(1);
+
+		(width, height)
+	}
+
+	/// Sets new dimensions for a single level picture of `image_size` bytes,
+	/// dropping any mipmap header and keeping the sizes and GS registers in step.
+	fn resize(&mut self, width: usize, height: usize, image_size: usize) {
+		if let Some(mipmaps) = self.mipmap_header.take() {
+			self.header_size -= MipmapHeader::size(mipmaps.sizes.len()) as u16;
+		}
+
+		self.width = width;
+		self.height = height;
+		self.mipmap_count = 1;
+		self.image_size = image_size as u32;
+		self.total_size = self.header_size as u32 + self.image_size + self.palette_size;
+
+		if self.gs_tex_0 != 0 {
+			let mut tex0 = self.tex0();
+
+			tex0.tw = width.next_power_of_two().trailing_zeros() as u8;
+			tex0.th = height.next_power_of_two().trailing_zeros() as u8;
+			self.gs_tex_0 = tex0.encode();
+		}
+
+		if self.gs_tex_1 != 0 {
+			let mut tex1 = self.tex1();
+
+			tex1.mxl = 0;
+			self.gs_tex_1 = tex1.encode();
+		}
+	}
+
+	fn swizzle_level<T: Default + Copy>(&self, data: &[T], level: usize, swizzled: bool) -> Vec::<T> {
+		let (width, height) = self.level_dimensions(level);
+
+		if swizzled {
+			swizzle::swizzle(data, width, height, self.psm())
+		} else {
+			data.to_vec()
+		}
+	}
+
+	/// Rebuilds level data from its stored order, optionally swapping every
+	/// nibble pair, and reads it back with or without the swizzle layout.
+	fn relayout<T: Default + Copy>(&self, data: &[T], level: usize, stored_swizzled: bool, swap_pairs: bool, swizzled: bool) -> Result<Vec::<T>, Error> {
+		let (width, height) = self.level_dimensions(level);
+		let mut stored = self.swizzle_level(data, level, stored_swizzled);
+
+		if swap_pairs {
+			for pair in stored.chunks_mut(2) {
+				pair.reverse();
+			}
+		}
+
+		if swizzled {
+			swizzle::unswizzle(&stored, width, height, self.psm())
+		} else {
+			Ok(stored)
+		}
+	}
+
+	pub fn psm(&self) -> Psm {
+		match self.tex0().psm() {
+			Some(psm) if psm.bpp() == self.bpp && (self.palette_size > 0 || !psm.is_indexed()) => psm,
+			_ => match self.bpp {
+				4 => Psm::Psmt4,
+				8 => Psm::Psmt8,
+				16 => Psm::Psmct16,
+				24 => Psm::Psmct24,
+				_ => Psm::Psmct32,
+			},
+		}
+	}
+
 	pub fn is_linear_palette(&self) -> bool {
 		self.clut_format & 0x80 != 0
 	}
@@ -102,79 +375,400 @@ impl Header {
 		}
 	}
 
+	/// Returns the format of the picture data, following `TEX0.PSM` when it
+	/// agrees with the stored bits per pixel.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::Format;
+	///
+	/// let image = tim2::load("../assets/test.tm2").unwrap();
+	/// assert_eq!(image.get_frame(0).info().pixel_format().unwrap(), Format::Indexed8);
+	/// ```
 	pub fn pixel_format(&self) -> Result<Format, Error> {
 		match self.bpp {
-			4 => Ok(Format::Indexed4),
-			8 => Ok(Format::Indexed8),
-			16 => Ok(Format::Abgr1555),
-			24 => Ok(Format::Rgb888),
-			32 => Ok(Format::Rgba8888),
-			n => Err(Error::InvalidBpp(n)),
+			4 | 8 | 16 | 24 | 32 => {},
+			n => return Err(Error::InvalidBpp(n)),
+		}
+
+		Ok(match self.psm() {
+			Psm::Psmt4 => Format::Indexed4,
+			Psm::Psmt8 => Format::Indexed8,
+			Psm::Psmct16 => Format::Abgr1555,
+			Psm::Psmct16s => Format::Abgr1555S,
+			Psm::Psmct24 => Format::Rgb888,
+			Psm::Psmct32 => Format::Rgba8888,
+			Psm::Psmt8h => Format::Indexed8H,
+			Psm::Psmt4hl => Format::Indexed4HL,
+			Psm::Psmt4hh => Format::Indexed4HH,
+		})
+	}
+
+	pub fn clut_color_format(&self) -> Option<Format> {
+		match self.clut_format & 0x3F {
+			1 => Some(Format::Abgr1555),
+			2 => Some(Format::Rgb888),
+			3 => Some(Format::Rgba8888),
+			_ => None,
+		}
+	}
+
+	/// Returns how the CLUT was loaded, from `TEX0.CSM`. CSM2 CLUTs are stored
+	/// linearly even when they aren't flagged so.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::ClutStorageMode;
+	///
+	/// let image = tim2::load("../assets/test.tm2").unwrap();
+	/// assert_eq!(image.get_frame(0).info().clut_storage_mode(), ClutStorageMode::Csm1);
+	/// ```
+	pub fn clut_storage_mode(&self) -> ClutStorageMode {
+		self.tex0().clut_storage_mode()
+	}
+
+	/* only CSM1 CLUTs that aren't flagged linear are stored in the 8 color stripe order */
+	fn is_striped_clut(&self, mode: ClutStorageMode) -> bool {
+		!self.is_linear_palette() && mode == ClutStorageMode::Csm1
+	}
+
+	/// Returns the first CLUT entry a 4bpp texture indexes into: `CSA * 16` in
+	/// CSM1, or the `TEXCLUT` offset inside a `CBW * 64` wide CLUT in CSM2.
+	/// Palettes of 16 colors or fewer are always used from their start.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let image = tim2::load("../assets/test.tm2").unwrap();
+	/// assert_eq!(image.get_frame(0).info().clut_offset(), 0);
+	/// ```
+	pub fn clut_offset(&self) -> usize {
+		self.clut_window(self.clut_storage_mode())
+	}
+
+	fn clut_window(&self, mode: ClutStorageMode) -> usize {
+		match mode {
+			ClutStorageMode::Csm1 => self.tex0().csa as usize * 16,
+			ClutStorageMode::Csm2 => {
+				let clut = self.tex_clut();
+
+				clut.cov as usize * clut.cbw as usize * 64 + clut.cou as usize * 16
+			},
+		}
+	}
+
+	pub fn total_size(&self) -> u32 {
+		self.total_size
+	}
+
+	pub fn palette_size(&self) -> u32 {
+		self.palette_size
+	}
+
+	pub fn image_size(&self) -> u32 {
+		self.image_size
+	}
+
+	pub fn header_size(&self) -> u16 {
+		self.header_size
+	}
+
+	pub fn color_entry_count(&self) -> u16 {
+		self.color_entry_count
+	}
+
+	pub fn paletted(&self) -> u8 {
+		self.paletted
+	}
+
+	pub fn mipmap_count(&self) -> u8 {
+		self.mipmap_count
+	}
+
+	pub fn clut_format(&self) -> u8 {
+		self.clut_format
+	}
+
+	pub fn bpp(&self) -> u8 {
+		self.bpp
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	pub fn gs_tex_0(&self) -> u64 {
+		self.gs_tex_0
+	}
+
+	pub fn gs_tex_1(&self) -> u64 {
+		self.gs_tex_1
+	}
+
+	pub fn gs_regs(&self) -> u32 {
+		self.gs_regs
+	}
+
+	pub fn gs_tex_clut(&self) -> u32 {
+		self.gs_tex_clut
+	}
+
+	pub fn tex0(&self) -> GsTex0 {
+		GsTex0::decode(self.gs_tex_0)
+	}
+
+	pub fn tex1(&self) -> GsTex1 {
+		GsTex1::decode(self.gs_tex_1)
+	}
+
+	pub fn tex_clut(&self) -> GsTexClut {
+		GsTexClut::decode(self.gs_tex_clut)
+	}
+
+	pub fn texa(&self) -> GsTexa {
+		GsTexa::decode(self.gs_regs)
+	}
+
+	/* a zero TEXA word is taken as unset rather than as fully transparent */
+	pub(crate) fn active_texa(&self) -> Option<GsTexa> {
+		if self.gs_regs != 0 { Some(self.texa()) } else { None }
+	}
+
+	pub(crate) fn check_region(&self, level: usize, x: usize, y: usize, width: usize, height: usize) -> Result<(), Error> {
+		let (level_width, level_height) = self.level_dimensions(level);
+		let right = x.checked_add(width).filter(|v| *v <= level_width);
+		let bottom = y.checked_add(height).filter(|v| *v <= level_height);
+
+		match (right, bottom) {
+			(Some(_), Some(_)) => Ok(()),
+			_ => Err(Error::InvalidRegion { x, y, width, height }),
+		}
+	}
+
+	pub fn mipmap_header(&self) -> Option<&MipmapHeader> {
+		self.mipmap_header.as_ref()
+	}
+
+	pub fn user_data(&self) -> &UserData {
+		&self.user_data
+	}
+}
+
+/// A mipmap level below the base image, at half the size of the one above.
+///
+/// # Examples
+///
+/// ```
+/// let image = tim2::load("../assets/test.tm2").unwrap();
+///
+/// for level in image.get_frame(0).mipmaps() {
+///     println!("{}x{}", level.width(), level.height());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MipLevel {
+	width: usize,
+	height: usize,
+	data: DataKind,
+	words: Vec::<u32>,
+}
+
+impl MipLevel {
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	pub fn data(&self) -> &DataKind {
+		&self.data
+	}
+}
+
+/// Turns stored indices and colors into final pixels: CLUT lookup, then
+/// `TEXA`, then the alpha mode.
+pub(crate) struct Resolver<'a> {
+	palette: Option<&'a [Pixel]>,
+	base: usize,
+	format: Option<Format>,
+	texa: Option<GsTexa>,
+	alpha_mode: AlphaMode,
+	color_key: Option<Pixel>,
+	nibble_order: NibbleOrder,
+}
+
+impl<'a> Resolver<'a> {
+	pub(crate) fn new(header: &FrameInfo, palette: Option<&'a [Pixel]>, options: &DecodeOptions) -> Resolver<'a> {
+		let storage = options.clut_storage.unwrap_or_else(|| header.clut_storage_mode());
+		let base = match palette {
+			Some(v) if matches!(header.psm(), Psm::Psmt4 | Psm::Psmt4hl | Psm::Psmt4hh) && v.len() > 16 => header.clut_window(storage),
+			_ => 0,
+		};
+
+		let format = if palette.is_some() {
+			header.clut_color_format()
+		} else {
+			header.pixel_format().ok()
+		};
+
+		Resolver {
+			palette,
+			base,
+			format,
+			texa: options.texa.or_else(|| header.active_texa()),
+			alpha_mode: options.alpha_mode,
+			color_key: options.color_key,
+			nibble_order: options.nibble_order,
+		}
+	}
+
+	fn color(&self, pixel: &Pixel) -> Pixel {
+		let pixel = match (&self.texa, self.format) {
+			(Some(texa), Some(format)) => pixel.with_texa(texa, format),
+			_ => *pixel,
+		};
+
+		let pixel = pixel.with_alpha_mode(self.alpha_mode);
+
+		match self.color_key {
+			Some(key) if key == pixel => Pixel::from(pixel.r(), pixel.g(), pixel.b(), 0),
+			_ => pixel,
+		}
+	}
+
+	fn index(&self, index: u8) -> Result<Pixel, Error> {
+		let index = self.base + index as usize;
+		let color = self.palette
+			.and_then(|v| v.get(index))
+			.ok_or(Error::PaletteIndexOutOfRange(index))?;
+
+		Ok(self.color(color))
+	}
+
+	fn resolve(&self, data: &DataKind, index: usize) -> Result<Pixel, Error> {
+		match data {
+			DataKind::Indices(v) => self.index(*v.get(index).ok_or(Error::InvalidRange(index))?),
+			DataKind::Pixels(v) => Ok(self.color(v.get(index).ok_or(Error::InvalidRange(index))?)),
+		}
+	}
+
+	/// Resolves texel `index` straight from image data as stored in the file.
+	pub(crate) fn resolve_raw(&self, header: &FrameInfo, data: &[u8], index: usize) -> Result<Pixel, Error> {
+		let byte = |i: usize| data.get(i).copied().ok_or(Error::InvalidRange(i));
+
+		match header.psm() {
+			Psm::Psmt4 => self.index(self.nibble_order.unpack(byte(index / 2)?)[index % 2]),
+			Psm::Psmt8 => self.index(byte(index)?),
+			Psm::Psmt8h => self.index(byte(index * 4 + 3)?),
+			Psm::Psmt4hl => self.index(byte(index * 4 + 3)? & 0xF),
+			Psm::Psmt4hh => self.index(byte(index * 4 + 3)? >> 4),
+			_ => {
+				let size = header.bpp as usize / 8;
+				let start = index * size;
+				let color = data.get(start..start + size).ok_or(Error::InvalidRange(start))?;
+
+				Ok(self.color(&Pixel::from_buf(color)?))
+			},
 		}
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Frame {
-	header: Header,
+	header: FrameInfo,
 	data: DataKind,
+	words: Vec::<u32>,
+	mipmaps: Vec::<MipLevel>,
 	palettes: Vec::<PixelBuffer>,
+	nibble_order: NibbleOrder,
+	alpha_mode: AlphaMode,
+	texa_override: Option<GsTexa>,
+	swizzled: bool,
 }
 
 impl Frame {
 	pub fn read(buffer: &[u8], offset: &mut usize) -> Result<Frame, Error> {
-		let header = Header::read(buffer, offset)?;
-		let data = Frame::read_data(buffer, offset, &header)?;
+		let header = FrameInfo::read(buffer, offset)?;
+		let (data, words) = Frame::read_data(buffer, offset, &header, 0)?;
+		let mut mipmaps = Vec::with_capacity(header.level_count() - 1);
+
+		for level in 1..header.level_count() {
+			let (width, height) = header.level_dimensions(level);
+			let (data, words) = Frame::read_data(buffer, offset, &header, level)?;
+
+			mipmaps.push(MipLevel { width, height, data, words });
+		}
+
 		let palettes= Frame::read_palettes(buffer, offset, &header)?;
 
-		Ok(Frame { header, data, palettes })
+		Ok(Frame {
+			header,
+			data,
+			words,
+			mipmaps,
+			palettes,
+			nibble_order: NibbleOrder::default(),
+			alpha_mode: AlphaMode::default(),
+			texa_override: None,
+			swizzled: false,
+		})
 	}
 
-	fn read_data(buffer: &[u8], offset: &mut usize, header: &Header) -> Result<DataKind, Error> {
+	/// Reads one level, along with the whole stored words of the high indexed
+	/// modes so the bits around each index can be written back.
+	fn read_data(buffer: &[u8], offset: &mut usize, header: &FrameInfo, level: usize) -> Result<(DataKind, Vec::<u32>), Error> {
 		let pixel_size = header.bpp as usize / 8;
-		let size = header.image_size as usize;
-		let slice = get_slice(buffer, offset, size);
-		let data = if header.bpp == 4 {
-			let mut result = Vec::with_capacity(slice.len() * 2);
-
-			for index_pair in slice {
-				result.push(*index_pair & 0xF0 >> 4);
-				result.push(*index_pair & 0xF);
-			}
+		let size = header.level_size(level);
+		let slice = get_slice(buffer, offset, size)?;
+		let data = match header.psm() {
+			Psm::Psmt4 => {
+				let mut result = Vec::with_capacity(slice.len() * 2);
 
-			result
-		} else {
-			slice.to_vec()
+				for index_pair in slice {
+					result.extend_from_slice(&NibbleOrder::default().unpack(*index_pair));
+				}
+
+				result
+			},
+			/* the high indexed modes keep their index in the top byte of each little-endian word */
+			Psm::Psmt8h => slice.chunks_exact(4).map(|v| v[3]).collect(),
+			Psm::Psmt4hl => slice.chunks_exact(4).map(|v| v[3] & 0xF).collect(),
+			Psm::Psmt4hh => slice.chunks_exact(4).map(|v| v[3] >> 4).collect(),
+			_ => slice.to_vec(),
 		};
 
-		if header.palette_size > 0 {
-			let raw = if header.gs_tex_0 & (1 << 55) != 0 {
-				Frame::unswizzle(&data, header)
-			} else {
-				data
-			};
+		let words = match header.psm() {
+			Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh => slice.chunks_exact(4).map(LittleEndian::read_u32).collect(),
+			_ => Vec::new(),
+		};
 
-			Ok(DataKind::Indices(raw))
+		if header.palette_size > 0 {
+			Ok((DataKind::Indices(data), words))
 		} else {
-			let colors = Frame::read_colors(&data, pixel_size)?;
-			let raw = if header.gs_tex_0 & (1 << 55) != 0 {
-				Frame::unswizzle(&colors, header)
-			} else {
-				colors
-			};
-
-			Ok(DataKind::Pixels(raw))
+			Ok((DataKind::Pixels(Frame::read_colors(&data, pixel_size)?), words))
 		}
 	}
 
-	fn read_palettes(buffer: &[u8], offset: &mut usize, header: &Header) -> Result<Vec<PixelBuffer>, Error> {
+	pub(crate) fn read_palettes(buffer: &[u8], offset: &mut usize, header: &FrameInfo) -> Result<Vec<PixelBuffer>, Error> {
 		if header.palette_size == 0 {
 			return Ok(Vec::new());
 		}
 
 		let total_size = header.palette_size as usize;
-		let slice = get_slice(buffer, offset, total_size);
+		let slice = get_slice(buffer, offset, total_size)?;
 		let size = header.color_entry_count as usize * header.color_size();
+		if size == 0 {
+			return Err(Error::InvalidPaletteSize(total_size));
+		}
+
 		let count = total_size / size;
 		let color_size = header.color_size();
 		let mut result = Vec::with_capacity(count);
@@ -185,7 +779,7 @@ impl Frame {
 			let data = &slice[start_index..end_index];
 			let mut palette = Frame::read_colors(data, color_size)?;
 
-			if !header.is_linear_palette() && header.bpp == 8 {
+			if header.is_striped_clut(header.clut_storage_mode()) {
 				Frame::linearize_palette(&mut palette);
 			}
 
@@ -199,8 +793,12 @@ impl Frame {
 		let mut offset = 0usize;
 		let mut result = Vec::new();
 
+		if color_size == 0 {
+			return Err(Error::InvalidBpp(0));
+		}
+
 		for _ in (0..buffer.len()).step_by(color_size) {
-			let slice = get_slice(buffer, &mut offset, color_size);
+			let slice = get_slice(buffer, &mut offset, color_size)?;
 			let pixel = Pixel::from_buf(slice)?;
 
 			result.push(pixel)
@@ -209,6 +807,84 @@ impl Frame {
 		Ok(result)
 	}
 
+	pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+		self.header.write(writer)?;
+		self.write_data(writer, &self.data, &self.words, 0)?;
+
+		for (i, mipmap) in self.mipmaps.iter().enumerate() {
+			self.write_data(writer, &mipmap.data, &mipmap.words, i + 1)?;
+		}
+
+		self.write_palettes(writer)
+	}
+
+	fn write_data<W: Write>(&self, writer: &mut W, data: &DataKind, words: &[u32], level: usize) -> Result<(), Error> {
+		match data {
+			DataKind::Indices(v) => {
+				let data = self.header.swizzle_level(v, level, self.swizzled);
+
+				match self.header.psm() {
+					Psm::Psmt4 => {
+						for index_pair in data.chunks(2) {
+							let first = index_pair[0];
+							let second = index_pair.get(1).map_or(0, |v| *v);
+
+							writer.write_u8(self.nibble_order.pack(first, second))?;
+						}
+					},
+					/* the other bits belong to whatever shares the words in GS memory, so only the index changes */
+					psm @ (Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh) => {
+						for (i, index) in data.iter().enumerate() {
+							let word = words.get(i).copied().unwrap_or(0);
+							let word = match psm {
+								Psm::Psmt4hl => word & !(0xF << 24) | (*index as u32 & 0xF) << 24,
+								Psm::Psmt4hh => word & !(0xF << 28) | (*index as u32 & 0xF) << 28,
+								_ => word & !(0xFF << 24) | (*index as u32) << 24,
+							};
+
+							writer.write_u32::<LittleEndian>(word)?;
+						}
+					},
+					_ => writer.write_all(&data)?,
+				}
+			},
+			DataKind::Pixels(v) => {
+				let pixel_size = self.header.bpp as usize / 8;
+				let colors = self.header.swizzle_level(v, level, self.swizzled);
+
+				Frame::write_colors(writer, &colors, pixel_size)?;
+			},
+		}
+
+		Ok(())
+	}
+
+	fn write_palettes<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+		let color_size = self.header.color_size();
+
+		for palette in &self.palettes {
+			if self.header.is_striped_clut(self.header.clut_storage_mode()) {
+				/* the CSM1 reorder swaps bits 3 and 4 of each index, so it is its own inverse */
+				let mut compound = palette.clone();
+
+				Frame::linearize_palette(&mut compound);
+				Frame::write_colors(writer, &compound, color_size)?;
+			} else {
+				Frame::write_colors(writer, palette, color_size)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn write_colors<W: Write>(writer: &mut W, colors: &[Pixel], color_size: usize) -> Result<(), Error> {
+		for color in colors {
+			writer.write_all(&color.to_buf(color_size)?)?;
+		}
+
+		Ok(())
+	}
+
 	fn linearize_palette(palette: &mut PixelBuffer) {
 		const COLOR_COUNT: usize = 8;
 		const BLOCK_COUNT: usize = 2;
@@ -234,41 +910,112 @@ impl Frame {
 		}
 	}
 
-	fn unswizzle<T: Default + Copy>(buffer: &Vec::<T>, header: &Header) -> Vec::<T> {
-		let mut i = 0usize;
-		let mut result = vec![Default::default(); buffer.len()];
-
-		for y in (0..header.height).step_by(SWIZZLE_HEIGHT) {
-			for x in (0..header.width).step_by(SWIZZLE_WIDTH) {
-				for tile_y in y..(y + SWIZZLE_HEIGHT) {
-					for tile_x in x..(x + SWIZZLE_WIDTH) {
-						if tile_x < header.width && tile_y < header.height {
-							let index = tile_y * header.width + tile_x;
-
-							if let Some(value) = buffer.get(i) {
-								result[index] = *value;
-							}
-						}
+	pub fn nibble_order(&self) -> NibbleOrder {
+		self.nibble_order
+	}
 
-						i += 1;
-					}
+	pub fn set_nibble_order(&mut self, order: NibbleOrder) {
+		if order == self.nibble_order || self.header.bpp != 4 {
+			self.nibble_order = order;
+			return;
+		}
+
+		/* swap every packed pair in storage order, which differs from texel order when swizzled */
+		let header = &self.header;
+		let swizzled = self.swizzled;
+		let levels = std::iter::once(&mut self.data).chain(self.mipmaps.iter_mut().map(|v| &mut v.data));
+
+		for (level, data) in levels.enumerate() {
+			/* the stored order comes from swizzling this same data, so it always covers the layout */
+			if let DataKind::Indices(v) = data {
+				if let Ok(relaid) = header.relayout(v, level, swizzled, true, swizzled) {
+					*v = relaid;
 				}
 			}
 		}
 
-		result
+		self.nibble_order = order;
+	}
+
+	pub fn alpha_mode(&self) -> AlphaMode {
+		self.alpha_mode
+	}
+
+	pub fn swizzled(&self) -> bool {
+		self.swizzled
+	}
+
+	/// Marks the stored data as laid out for a `PSMCT32` transfer, or not, and
+	/// reorders the texels to match. TIM2 has no flag for this, so frames are
+	/// read unswizzled; the writer stores them back the way they were set.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut image = tim2::load("../assets/test.tm2").unwrap();
+	/// let plain = image.get_frame(0).get_pixels().unwrap();
+	///
+	/// image.frames_mut()[0].set_swizzled(true).unwrap();
+	/// assert_ne!(image.get_frame(0).get_pixels().unwrap(), plain);
+	///
+	/// /* the stored bytes don't change */
+	/// let mut output = Vec::new();
+	/// image.write(&mut output).unwrap();
+	/// assert_eq!(output, std::fs::read("../assets/test.tm2").unwrap()[..output.len()]);
+	/// ```
+	pub fn set_swizzled(&mut self, swizzled: bool) -> Result<(), Error> {
+		if swizzled == self.swizzled {
+			return Ok(());
+		}
+
+		/* lay out every level before replacing any, so a failure leaves the frame as it was */
+		let header = &self.header;
+		let relaid = std::iter::once(&self.data).chain(self.mipmaps.iter().map(|v| &v.data))
+			.enumerate()
+			.map(|(level, data)| Ok(match data {
+				DataKind::Indices(v) => DataKind::Indices(header.relayout(v, level, self.swizzled, false, swizzled)?),
+				DataKind::Pixels(v) => DataKind::Pixels(header.relayout(v, level, self.swizzled, false, swizzled)?),
+			}))
+			.collect::<Result<Vec::<DataKind>, Error>>()?;
+
+		let levels = std::iter::once(&mut self.data).chain(self.mipmaps.iter_mut().map(|v| &mut v.data));
+		for (data, relaid) in levels.zip(relaid) {
+			*data = relaid;
+		}
+
+		self.swizzled = swizzled;
+		Ok(())
+	}
+
+	pub fn set_alpha_mode(&mut self, mode: AlphaMode) {
+		self.alpha_mode = mode;
+	}
+
+	pub fn texa_override(&self) -> Option<GsTexa> {
+		self.texa_override
+	}
+
+	/// Replaces the `TEXA` values used to decode 16 and 24 bit colors. With no
+	/// override, the header's `TEXA` is used unless it is zero, in which case
+	/// the stored alpha bit is kept as is.
+	pub fn set_texa_override(&mut self, texa: Option<GsTexa>) {
+		self.texa_override = texa;
 	}
 
 	pub fn has_mipmaps(&self) -> bool {
 		self.header.mipmap_count > 1
 	}
 
+	pub fn mipmaps(&self) -> &[MipLevel] {
+		&self.mipmaps
+	}
+
 	pub fn width(&self) -> usize {
-		self.header.width as usize
+		self.header.width
 	}
 
 	pub fn height(&self) -> usize {
-		self.header.height as usize
+		self.header.height
 	}
 
 	pub fn format(&self) -> Result<Format, Error> {
@@ -279,39 +1026,417 @@ impl Frame {
 		&self.data
 	}
 
-	pub fn get_pixels(&self) -> PixelBuffer {
-		match &self.data {
-			DataKind::Indices(v) => {
-				let palette = &self.palettes[0];
-				let mut result = Vec::with_capacity(v.len());
+	pub fn info(&self) -> &FrameInfo {
+		&self.header
+	}
+
+	pub fn tex0(&self) -> GsTex0 {
+		self.header.tex0()
+	}
+
+	pub fn tex1(&self) -> GsTex1 {
+		self.header.tex1()
+	}
+
+	pub fn tex_clut(&self) -> GsTexClut {
+		self.header.tex_clut()
+	}
+
+	pub fn texa(&self) -> GsTexa {
+		self.header.texa()
+	}
+
+	pub fn palettes(&self) -> &[PixelBuffer] {
+		&self.palettes
+	}
+
+	pub fn palette_count(&self) -> usize {
+		self.palettes.len()
+	}
+
+	/// Returns the top level colors of a true color picture for editing.
+	pub fn pixels_mut(&mut self) -> Option<&mut [Pixel]> {
+		match &mut self.data {
+			DataKind::Pixels(v) => Some(v),
+			DataKind::Indices(_) => None,
+		}
+	}
+
+	/// Returns the top level palette indices of an indexed picture for editing.
+	pub fn indices_mut(&mut self) -> Option<&mut [u8]> {
+		match &mut self.data {
+			DataKind::Indices(v) => Some(v),
+			DataKind::Pixels(_) => None,
+		}
+	}
 
-				for index in v {
-					result.push(palette[*index as usize]);
+	pub fn palette_mut(&mut self, index: usize) -> Result<&mut [Pixel], Error> {
+		match self.palettes.get_mut(index) {
+			Some(v) => Ok(v),
+			None => Err(Error::PaletteNotFound(index)),
+		}
+	}
+
+	pub fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) -> Result<(), Error> {
+		self.header.check_region(0, x, y, 1, 1)?;
+
+		let index = y * self.header.width + x;
+		let pixels = self.pixels_mut().ok_or(Error::DataKindMismatch)?;
+
+		/* the stored data can be shorter than the dimensions say */
+		*pixels.get_mut(index).ok_or(Error::InvalidRange(index))? = pixel;
+		Ok(())
+	}
+
+	pub fn set_index(&mut self, x: usize, y: usize, index: u8) -> Result<(), Error> {
+		self.header.check_region(0, x, y, 1, 1)?;
+		self.check_indices(&[index])?;
+
+		let offset = y * self.header.width + x;
+		let indices = self.indices_mut().ok_or(Error::DataKindMismatch)?;
+
+		*indices.get_mut(offset).ok_or(Error::InvalidRange(offset))? = index;
+		Ok(())
+	}
+
+	fn check_indices(&self, indices: &[u8]) -> Result<(), Error> {
+		let limit = match self.header.psm() {
+			Psm::Psmt4 | Psm::Psmt4hl | Psm::Psmt4hh => 16,
+			_ => 256,
+		};
+
+		match indices.iter().find(|v| **v as usize >= limit) {
+			Some(v) => Err(Error::PaletteIndexOutOfRange(*v as usize)),
+			None => Ok(()),
+		}
+	}
+
+	/// Replaces the picture with `width` by `height` texels of the same kind,
+	/// indices for indexed pictures and colors otherwise. Mipmaps are dropped,
+	/// and the header sizes and `TEX0` dimensions are updated to match.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::DataKind;
+	///
+	/// fn main() {
+	///     let mut image = tim2::load("../assets/test.tm2").unwrap();
+	///     let frame = &mut image.frames_mut()[0];
+	///
+	///     frame.replace_data(32, 16, DataKind::Indices(vec![1; 32 * 16])).unwrap();
+	///     frame.set_index(0, 0, 2).unwrap();
+	///
+	///     let mut buffer = Vec::new();
+	///     image.write(&mut buffer).unwrap();
+	///
+	///     let info = tim2::from_buffer(&buffer).unwrap().get_frame(0).info().clone();
+	///     assert_eq!(info.image_size(), 32 * 16);
+	///     assert_eq!(info.total_size(), 48 + 32 * 16 + 1024);
+	/// }
+	/// ```
+	pub fn replace_data(&mut self, width: usize, height: usize, data: DataKind) -> Result<(), Error> {
+		if width == 0 || width > u16::MAX as usize {
+			return Err(Error::InvalidRange(width));
+		}
+
+		if height == 0 || height > u16::MAX as usize {
+			return Err(Error::InvalidRange(height));
+		}
+
+		if data.len() != width * height {
+			return Err(Error::InvalidRange(data.len()));
+		}
+
+		match (&self.data, &data) {
+			(DataKind::Indices(_), DataKind::Indices(v)) => self.check_indices(v)?,
+			(DataKind::Pixels(_), DataKind::Pixels(_)) => (),
+			_ => return Err(Error::DataKindMismatch),
+		}
+
+		let mut header = self.header.clone();
+		header.resize(width, height, 0);
+
+		/* measure the stored size with the writer so the two always agree */
+		let previous = std::mem::replace(&mut self.header, header);
+		let mut bytes = Vec::new();
+
+		if let Err(err) = self.write_data(&mut bytes, &data, &[], 0) {
+			self.header = previous;
+			return Err(err);
+		}
+
+		self.header.resize(width, height, bytes.len());
+		self.data = data;
+		self.words.clear();
+		self.mipmaps.clear();
+		Ok(())
+	}
+
+	/// Returns the options `get_pixels`, `to_raw` and friends decode with:
+	/// the frame's nibble order, alpha mode and `TEXA` override.
+	pub fn decode_options(&self) -> DecodeOptions {
+		DecodeOptions {
+			nibble_order: self.nibble_order,
+			alpha_mode: self.alpha_mode,
+			texa: self.texa_override,
+			..Default::default()
+		}
+	}
+
+	/// Decodes one level of the frame to pixels.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::{DecodeOptions, SwizzleMode};
+	///
+	/// fn main() {
+	///     let image = tim2::load("../assets/test.tm2").unwrap();
+	///     let frame = image.get_frame(0);
+	///
+	///     /* reading the data through the swizzle layout reorders it */
+	///     let options = DecodeOptions::new().swizzle(SwizzleMode::ForceOn);
+	///     let pixels = frame.decode(&options).unwrap();
+	///     assert_ne!(pixels, frame.get_pixels().unwrap());
+	///
+	///     assert!(frame.decode(&DecodeOptions::new().palette_index(1)).is_err());
+	/// }
+	/// ```
+	pub fn decode(&self, options: &DecodeOptions) -> Result<PixelBuffer, Error> {
+		let data = self.level_data(options)?;
+		let palette = self.level_palette(options)?;
+		let resolver = Resolver::new(&self.header, palette.as_deref(), options);
+		let (width, height) = self.header.level_dimensions(options.mip_level);
+
+		/* swizzled data is padded out to whole blocks, past the level's own texels */
+		(0..data.len().min(width * height)).map(|i| resolver.resolve(&data, i)).collect()
+	}
+
+	pub fn get_pixels(&self) -> Result<PixelBuffer, Error> {
+		self.get_pixels_with_palette(0)
+	}
+
+	pub fn get_pixels_with_palette(&self, palette_index: usize) -> Result<PixelBuffer, Error> {
+		self.decode(&self.decode_options().palette_index(palette_index))
+	}
+
+	pub fn get_mipmap_pixels(&self, index: usize) -> Result<PixelBuffer, Error> {
+		self.mipmaps.get(index).ok_or(Error::MipmapNotFound(index))?;
+
+		self.decode(&self.decode_options().mip_level(index + 1))
+	}
+
+	/// Returns the data of the requested level, laid out again when the
+	/// options read it differently from how the frame was loaded.
+	fn level_data(&self, options: &DecodeOptions) -> Result<Cow<'_, DataKind>, Error> {
+		let level = options.mip_level;
+		let data = self.level(level)?;
+
+		let swizzled = options.swizzle.resolve(self.swizzled);
+		let swap_pairs = self.header.psm() == Psm::Psmt4 && options.nibble_order != self.nibble_order;
+
+		if swizzled == self.swizzled && !swap_pairs {
+			return Ok(Cow::Borrowed(data));
+		}
+
+		Ok(Cow::Owned(match data {
+			DataKind::Indices(v) => DataKind::Indices(self.header.relayout(v, level, self.swizzled, swap_pairs, swizzled)?),
+			DataKind::Pixels(v) => DataKind::Pixels(self.header.relayout(v, level, self.swizzled, false, swizzled)?),
+		}))
+	}
+
+	fn level(&self, level: usize) -> Result<&DataKind, Error> {
+		match level {
+			0 => Ok(&self.data),
+			n => Ok(&self.mipmaps.get(n - 1).ok_or(Error::MipmapNotFound(n))?.data),
+		}
+	}
+
+	fn level_palette(&self, options: &DecodeOptions) -> Result<Option<Cow<'_, [Pixel]>>, Error> {
+		Frame::select_palette(&self.header, &self.palettes, options)
+	}
+
+	/// Picks the palette `options` asks for out of those read for `header`.
+	pub(crate) fn select_palette<'p>(header: &FrameInfo, palettes: &'p [PixelBuffer], options: &DecodeOptions) -> Result<Option<Cow<'p, [Pixel]>>, Error> {
+		if header.palette_size == 0 {
+			return Ok(None);
+		}
+
+		let index = options.palette_index;
+		let palette = palettes.get(index).ok_or(Error::PaletteNotFound(index))?;
+
+		let header_mode = header.clut_storage_mode();
+		let mode = options.clut_storage.unwrap_or(header_mode);
+
+		if header.is_striped_clut(mode) != header.is_striped_clut(header_mode) {
+			/* redo or undo the CSM1 unshuffle done on read, which is its own inverse */
+			let mut stored = palette.clone();
+
+			Frame::linearize_palette(&mut stored);
+			Ok(Some(Cow::Owned(stored)))
+		} else {
+			Ok(Some(Cow::Borrowed(palette)))
+		}
+	}
+
+	/// Decodes the `width` by `height` rectangle at `(x, y)` of the level the
+	/// options select, resolving only the pixels inside it.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::{DecodeOptions, SwizzleMode};
+	///
+	/// fn main() {
+	///     let image = tim2::load("../assets/test.tm2").unwrap();
+	///     let frame = image.get_frame(0);
+	///     let options = frame.decode_options();
+	///
+	///     let cell = frame.decode_region(16, 8, 8, 4, &options).unwrap();
+	///     let pixels = frame.get_pixels().unwrap();
+	///     assert_eq!(cell.len(), 32);
+	///     assert_eq!(cell[9], pixels[9 * 64 + 17]);
+	///
+	///     assert!(frame.decode_region(60, 0, 8, 8, &options).is_err());
+	///
+	///     let swizzled = DecodeOptions::new().swizzle(SwizzleMode::ForceOn);
+	///     let cell = frame.decode_region(16, 8, 8, 4, &swizzled).unwrap();
+	///     assert_eq!(cell[9], frame.decode(&swizzled).unwrap()[9 * 64 + 17]);
+	/// }
+	/// ```
+	pub fn decode_region(&self, x: usize, y: usize, width: usize, height: usize, options: &DecodeOptions) -> Result<PixelBuffer, Error> {
+		let data = self.level(options.mip_level)?;
+		self.header.check_region(options.mip_level, x, y, width, height)?;
+
+		let (level_width, level_height) = self.header.level_dimensions(options.mip_level);
+		let palette = self.level_palette(options)?;
+		let resolver = Resolver::new(&self.header, palette.as_deref(), options);
+		let swizzled = options.swizzle.resolve(self.swizzled);
+		let swap_pairs = self.header.psm() == Psm::Psmt4 && options.nibble_order != self.nibble_order;
+		let map = if swizzled != self.swizzled || swap_pairs {
+			Some(SwizzleMap::new(self.header.psm(), level_width, level_height))
+		} else {
+			None
+		};
+
+		/* a swizzled frame holds its texels unswizzled, so reading it in stored order needs the way back */
+		let texels = match &map {
+			Some(map) if self.swizzled => {
+				let mut texels = vec![None; map.stored_len()];
+
+				for row in 0..level_height {
+					for column in 0..level_width {
+						if let Some(slot) = map.index(column, row).and_then(|i| texels.get_mut(i)) {
+							*slot = Some(row * level_width + column);
+						}
+					}
 				}
 
-				result
+				Some(texels)
 			},
-			DataKind::Pixels(v) => v.to_vec(),
+			_ => None,
+		};
+
+		let mut result = Vec::with_capacity(width * height);
+
+		for row in y..y + height {
+			for column in x..x + width {
+				let index = match &map {
+					Some(map) => {
+						let stored = if swizzled {
+							map.index(column, row).ok_or(Error::InvalidRange(column))?
+						} else {
+							row * level_width + column
+						};
+
+						let stored = if swap_pairs { stored ^ 1 } else { stored };
+
+						match &texels {
+							Some(texels) => texels.get(stored).copied().flatten().ok_or(Error::InvalidRange(stored))?,
+							None => stored,
+						}
+					},
+					None => row * level_width + column,
+				};
+
+				result.push(resolver.resolve(data, index)?);
+			}
 		}
+
+		Ok(result)
 	}
 
-	pub fn to_raw(&self, color_key: Option<Pixel>) -> Vec::<u8> {
-		let pixels = self.get_pixels();
+	/// Decodes the level the options select into `buffer`, writing each row
+	/// `stride` bytes after the previous one.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::PixelLayout;
+	///
+	/// fn main() {
+	///     let image = tim2::load("../assets/test.tm2").unwrap();
+	///     let frame = image.get_frame(0);
+	///
+	///     /* rows padded out to 256 bytes, as a texture upload might need */
+	///     let stride = 256;
+	///     let mut buffer = vec![0u8; stride * frame.height()];
+	///     frame.decode_into(&mut buffer, PixelLayout::Bgra8, stride, &frame.decode_options()).unwrap();
+	///
+	///     let pixel = frame.get_pixels().unwrap()[65];
+	///     assert_eq!(&buffer[stride + 4..stride + 8], &[pixel.b(), pixel.g(), pixel.r(), pixel.a()]);
+	/// }
+	/// ```
+	pub fn decode_into(&self, buffer: &mut [u8], layout: PixelLayout, stride: usize, options: &DecodeOptions) -> Result<(), Error> {
+		let (width, height) = self.header.level_dimensions(options.mip_level);
+		let pixel_size = layout.size();
+		let row_size = width * pixel_size;
+
+		if stride < row_size {
+			return Err(Error::InvalidStride(stride));
+		}
+
+		let needed = match height {
+			0 => Some(0),
+			n => stride.checked_mul(n - 1).and_then(|v| v.checked_add(row_size)),
+		};
+
+		let needed = needed.ok_or(Error::InvalidStride(stride))?;
+		if buffer.len() < needed {
+			return Err(Error::BufferTooSmall { needed, found: buffer.len() });
+		}
+
+		let data = self.level_data(options)?;
+		let palette = self.level_palette(options)?;
+		let resolver = Resolver::new(&self.header, palette.as_deref(), options);
+
+		for y in 0..height {
+			let row = &mut buffer[y * stride..y * stride + row_size];
+
+			for (x, target) in row.chunks_exact_mut(pixel_size).enumerate() {
+				let pixel = resolver.resolve(&data, y * width + x)?;
+
+				layout.write(&pixel, target);
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn to_raw(&self, color_key: Option<Pixel>) -> Result<Vec::<u8>, Error> {
+		self.to_raw_with_palette(0, color_key)
+	}
+
+	pub fn to_raw_with_palette(&self, palette_index: usize, color_key: Option<Pixel>) -> Result<Vec::<u8>, Error> {
+		let options = self.decode_options().palette_index(palette_index).color_key(color_key);
+		let pixels = self.decode(&options)?;
 		let mut result = Vec::with_capacity(pixels.len() * 4);
 
 		for pixel in pixels {
-			let alpha = if let Some(v) = color_key {
-				if v != pixel { pixel.a() } else { 0 }
-			} else {
-				pixel.a()
-			};
-
-			result.push(pixel.r());
-			result.push(pixel.g());
-			result.push(pixel.b());
-			result.push(alpha);
+			result.extend_from_slice(&[pixel.r(), pixel.g(), pixel.b(), pixel.a()]);
 		}
 
-		result
+		Ok(result)
 	}
 }
@@ -0,0 +1,237 @@
+fn bits(value: u64, shift: u32, width: u32) -> u64 {
+	(value >> shift) & ((1 << width) - 1)
+}
+
+fn field(value: u64, shift: u32, width: u32) -> u64 {
+	(value & ((1 << width) - 1)) << shift
+}
+
+/// GS pixel storage modes, as found in `TEX0.PSM`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Psm {
+	Psmct32,
+	Psmct24,
+	Psmct16,
+	Psmct16s,
+	Psmt8,
+	Psmt4,
+	Psmt8h,
+	Psmt4hl,
+	Psmt4hh,
+}
+
+impl Psm {
+	pub fn from_id(id: u8) -> Option<Psm> {
+		match id {
+			0x00 => Some(Psm::Psmct32),
+			0x01 => Some(Psm::Psmct24),
+			0x02 => Some(Psm::Psmct16),
+			0x0A => Some(Psm::Psmct16s),
+			0x13 => Some(Psm::Psmt8),
+			0x14 => Some(Psm::Psmt4),
+			0x1B => Some(Psm::Psmt8h),
+			0x24 => Some(Psm::Psmt4hl),
+			0x2C => Some(Psm::Psmt4hh),
+			_ => None,
+		}
+	}
+
+	pub fn id(&self) -> u8 {
+		match self {
+			Psm::Psmct32 => 0x00,
+			Psm::Psmct24 => 0x01,
+			Psm::Psmct16 => 0x02,
+			Psm::Psmct16s => 0x0A,
+			Psm::Psmt8 => 0x13,
+			Psm::Psmt4 => 0x14,
+			Psm::Psmt8h => 0x1B,
+			Psm::Psmt4hl => 0x24,
+			Psm::Psmt4hh => 0x2C,
+		}
+	}
+
+	/// Returns the bits each texel occupies in memory. The high indexed modes
+	/// keep their index in the upper bits of a 32 bit word.
+	pub fn bpp(&self) -> u8 {
+		match self {
+			Psm::Psmct32 => 32,
+			Psm::Psmct24 => 24,
+			Psm::Psmct16 | Psm::Psmct16s => 16,
+			Psm::Psmt8 => 8,
+			Psm::Psmt4 => 4,
+			Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh => 32,
+		}
+	}
+
+	pub fn is_indexed(&self) -> bool {
+		matches!(self, Psm::Psmt8 | Psm::Psmt4 | Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh)
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClutStorageMode {
+	Csm1,
+	Csm2,
+}
+
+/// The GS `TEX0` register: texture buffer, pixel storage format and CLUT setup.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct GsTex0 {
+	pub tbp0: u16,
+	pub tbw: u8,
+	pub psm: u8,
+	pub tw: u8,
+	pub th: u8,
+	pub tcc: u8,
+	pub tfx: u8,
+	pub cbp: u16,
+	pub cpsm: u8,
+	pub csm: u8,
+	pub csa: u8,
+	pub cld: u8,
+}
+
+impl GsTex0 {
+	pub fn decode(value: u64) -> GsTex0 {
+		GsTex0 {
+			tbp0: bits(value, 0, 14) as u16,
+			tbw: bits(value, 14, 6) as u8,
+			psm: bits(value, 20, 6) as u8,
+			tw: bits(value, 26, 4) as u8,
+			th: bits(value, 30, 4) as u8,
+			tcc: bits(value, 34, 1) as u8,
+			tfx: bits(value, 35, 2) as u8,
+			cbp: bits(value, 37, 14) as u16,
+			cpsm: bits(value, 51, 4) as u8,
+			csm: bits(value, 55, 1) as u8,
+			csa: bits(value, 56, 5) as u8,
+			cld: bits(value, 61, 3) as u8,
+		}
+	}
+
+	pub fn encode(&self) -> u64 {
+		field(self.tbp0 as u64, 0, 14) |
+		field(self.tbw as u64, 14, 6) |
+		field(self.psm as u64, 20, 6) |
+		field(self.tw as u64, 26, 4) |
+		field(self.th as u64, 30, 4) |
+		field(self.tcc as u64, 34, 1) |
+		field(self.tfx as u64, 35, 2) |
+		field(self.cbp as u64, 37, 14) |
+		field(self.cpsm as u64, 51, 4) |
+		field(self.csm as u64, 55, 1) |
+		field(self.csa as u64, 56, 5) |
+		field(self.cld as u64, 61, 3)
+	}
+
+	pub fn psm(&self) -> Option<Psm> {
+		Psm::from_id(self.psm)
+	}
+
+	pub fn clut_storage_mode(&self) -> ClutStorageMode {
+		if self.csm == 0 {
+			ClutStorageMode::Csm1
+		} else {
+			ClutStorageMode::Csm2
+		}
+	}
+}
+
+/// The GS `TEX1` register: LOD calculation and filtering.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct GsTex1 {
+	pub lcm: u8,
+	pub mxl: u8,
+	pub mmag: u8,
+	pub mmin: u8,
+	pub mtba: u8,
+	pub l: u8,
+	pub k: u16,
+}
+
+impl GsTex1 {
+	pub fn decode(value: u64) -> GsTex1 {
+		GsTex1 {
+			lcm: bits(value, 0, 1) as u8,
+			mxl: bits(value, 2, 3) as u8,
+			mmag: bits(value, 5, 1) as u8,
+			mmin: bits(value, 6, 3) as u8,
+			mtba: bits(value, 9, 1) as u8,
+			l: bits(value, 19, 2) as u8,
+			k: bits(value, 32, 12) as u16,
+		}
+	}
+
+	pub fn encode(&self) -> u64 {
+		field(self.lcm as u64, 0, 1) |
+		field(self.mxl as u64, 2, 3) |
+		field(self.mmag as u64, 5, 1) |
+		field(self.mmin as u64, 6, 3) |
+		field(self.mtba as u64, 9, 1) |
+		field(self.l as u64, 19, 2) |
+		field(self.k as u64, 32, 12)
+	}
+}
+
+/// The GS `TEXCLUT` register: CLUT buffer width and offset used with CSM2.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct GsTexClut {
+	pub cbw: u8,
+	pub cou: u8,
+	pub cov: u16,
+}
+
+impl GsTexClut {
+	pub fn decode(value: u32) -> GsTexClut {
+		let value = value as u64;
+
+		GsTexClut {
+			cbw: bits(value, 0, 6) as u8,
+			cou: bits(value, 6, 6) as u8,
+			cov: bits(value, 12, 10) as u16,
+		}
+	}
+
+	pub fn encode(&self) -> u32 {
+		let value = field(self.cbw as u64, 0, 6) |
+			field(self.cou as u64, 6, 6) |
+			field(self.cov as u64, 12, 10);
+
+		value as u32
+	}
+}
+
+/// The GS `TEXA` register as packed by TIM2, which stores the `FBA` and `PABE`
+/// flags in the otherwise unused upper bits of the same word.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct GsTexa {
+	pub ta0: u8,
+	pub aem: u8,
+	pub ta1: u8,
+	pub pabe: u8,
+	pub fba: u8,
+}
+
+impl GsTexa {
+	pub fn decode(value: u32) -> GsTexa {
+		let value = value as u64;
+
+		GsTexa {
+			ta0: bits(value, 0, 8) as u8,
+			aem: bits(value, 15, 1) as u8,
+			ta1: bits(value, 16, 8) as u8,
+			pabe: bits(value, 30, 1) as u8,
+			fba: bits(value, 31, 1) as u8,
+		}
+	}
+
+	pub fn encode(&self) -> u32 {
+		let value = field(self.ta0 as u64, 0, 8) |
+			field(self.aem as u64, 15, 1) |
+			field(self.ta1 as u64, 16, 8) |
+			field(self.pabe as u64, 30, 1) |
+			field(self.fba as u64, 31, 1);
+
+		value as u32
+	}
+}
@@ -1,34 +1,106 @@
 use crate::common::*;
 use crate::error::Error;
-use crate::frame::Frame;
+use crate::frame::{Frame, FrameInfo, NibbleOrder, PixelBuffer, Resolver, HEADER_SIZE as PICTURE_HEADER_SIZE};
+use crate::gs::GsTexa;
+use crate::limits::Limits;
+use crate::options::DecodeOptions;
+use crate::pixel::AlphaMode;
+use crate::swizzle::SwizzleMap;
 
-use byteorder::{ByteOrder, BigEndian, LittleEndian};
+use byteorder::{ByteOrder, BigEndian, LittleEndian, WriteBytesExt};
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, SeekFrom};
 use std::path::Path;
 
 const IDENT: u32 = 0x54494d32;
+pub(crate) const HEADER_SIZE: usize = 16;
+
+/// The boundary each picture block starts on, set by the byte after the
+/// version in the file header.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::Alignment;
+///
+/// let image = tim2::load("../assets/test.tm2").unwrap();
+/// assert_eq!(image.alignment(), Alignment::Align16);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Alignment {
+	Align16,
+	Align128,
+}
+
+impl Alignment {
+	fn from_id(id: u8) -> Result<Alignment, Error> {
+		match id {
+			0 => Ok(Alignment::Align16),
+			1 => Ok(Alignment::Align128),
+			n => Err(Error::InvalidAlignment(n)),
+		}
+	}
+
+	fn id(&self) -> u8 {
+		match self {
+			Alignment::Align16 => 0,
+			Alignment::Align128 => 1,
+		}
+	}
+
+	pub fn size(&self) -> usize {
+		match self {
+			Alignment::Align16 => 16,
+			Alignment::Align128 => 128,
+		}
+	}
+}
 
 #[derive(Debug)]
-struct Header {
+pub(crate) struct Header {
 	identifier: u32,
-	version: u16,
-	count: usize,
+	pub(crate) version: u8,
+	pub(crate) alignment: Alignment,
+	pub(crate) count: usize,
 }
 
 impl Header {
-	fn read(buffer: &[u8], offset: &mut usize) -> Result<Header, Error> {
-		let mut load_part = |size| { get_slice(&buffer, offset, size) };
-		let identifier = BigEndian::read_u32(load_part(4));
-		let version = LittleEndian::read_u16(load_part(2));
-		let count = LittleEndian::read_u16(load_part(2)) as usize;
+	pub(crate) fn read(buffer: &[u8], offset: &mut usize) -> Result<Header, Error> {
+		let mut load_part = |size| { get_slice(buffer, offset, size) };
+		let identifier = BigEndian::read_u32(load_part(4)?);
+		let version = load_part(1)?[0];
+		let alignment_id = load_part(1)?[0];
+		let count = LittleEndian::read_u16(load_part(2)?) as usize;
 
-		load_part(8);
+		load_part(8)?;
 		if identifier != IDENT {
 			return Err(Error::InvalidIdentifier(identifier))
 		}
 
-		Ok(Header { identifier, version, count })
+		let alignment = Alignment::from_id(alignment_id)?;
+
+		Ok(Header { identifier, version, alignment, count })
+	}
+
+	/* the picture count is stored in 16 bits */
+	fn check_count(count: usize) -> Result<(), Error> {
+		if count > u16::MAX as usize {
+			Err(Error::LimitExceeded { name: "picture count", value: count, limit: u16::MAX as usize })
+		} else {
+			Ok(())
+		}
+	}
+
+	fn write<W: Write>(&self, writer: &mut W, count: usize) -> Result<(), Error> {
+		Header::check_count(count)?;
+		writer.write_u32::<BigEndian>(self.identifier)?;
+		writer.write_u8(self.version)?;
+		writer.write_u8(self.alignment.id())?;
+		writer.write_u16::<LittleEndian>(count as u16)?;
+		writer.write_all(&[0u8; 8])?;
+
+		Ok(())
 	}
 }
 
@@ -39,17 +111,82 @@ pub struct Image {
 }
 
 impl Image {
-	fn read(buffer: &[u8], offset: &mut usize) -> Result<Image, Error> {
+	fn read(buffer: &[u8], offset: &mut usize, limits: &Limits) -> Result<Image, Error> {
 		let header = Header::read(buffer, offset)?;
+		let alignment = header.alignment.size();
+
+		limits.check_pictures(header.count)?;
 		let mut frames = Vec::with_capacity(header.count);
+		let mut total = 0usize;
+
+		/* 128 byte aligned files pad out the file header and every picture block */
+		*offset = align(*offset, alignment);
+		for index in 0..header.count {
+			/* broken headers are left for Frame::read to report */
+			if let Ok(info) = FrameInfo::read(buffer, &mut offset.clone()) {
+				let bytes = limits.check_picture(&info).map_err(|err| err.in_picture(index, *offset))?;
+
+				total = total.saturating_add(bytes);
+				limits.check_alloc(total).map_err(|err| err.in_picture(index, *offset))?;
+			}
 
-		for _ in 0..header.count {
-			frames.push(Frame::read(buffer, offset)?);
+			frames.push(Frame::read(buffer, offset).map_err(|err| err.in_picture(index, *offset))?);
+			*offset = align(*offset, alignment);
 		}
 
 		Ok(Image { header, frames })
 	}
 
+	pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+		let alignment = self.header.alignment.size();
+		let mut block = Vec::new();
+
+		self.header.write(&mut block, self.frames.len())?;
+		for frame in &self.frames {
+			block.resize(align(block.len(), alignment), 0);
+			writer.write_all(&block)?;
+			block.clear();
+			frame.write(&mut block)?;
+		}
+
+		writer.write_all(&block)?;
+		Ok(())
+	}
+
+	pub fn version(&self) -> u8 {
+		self.header.version
+	}
+
+	pub fn alignment(&self) -> Alignment {
+		self.header.alignment
+	}
+
+	pub fn set_nibble_order(&mut self, order: NibbleOrder) {
+		for frame in &mut self.frames {
+			frame.set_nibble_order(order);
+		}
+	}
+
+	pub fn set_alpha_mode(&mut self, mode: AlphaMode) {
+		for frame in &mut self.frames {
+			frame.set_alpha_mode(mode);
+		}
+	}
+
+	pub fn set_swizzled(&mut self, swizzled: bool) -> Result<(), Error> {
+		for frame in &mut self.frames {
+			frame.set_swizzled(swizzled)?;
+		}
+
+		Ok(())
+	}
+
+	pub fn set_texa_override(&mut self, texa: Option<GsTexa>) {
+		for frame in &mut self.frames {
+			frame.set_texa_override(texa);
+		}
+	}
+
 	pub fn frames(&self) -> &Vec::<Frame> {
 		&self.frames
 	}
@@ -57,6 +194,457 @@ impl Image {
 	pub fn get_frame(&self, index: usize) -> &Frame {
 		&self.frames[index]
 	}
+
+	pub fn frames_mut(&mut self) -> &mut [Frame] {
+		&mut self.frames
+	}
+
+	pub fn push_frame(&mut self, frame: Frame) -> Result<(), Error> {
+		Header::check_count(self.frames.len() + 1)?;
+		self.frames.push(frame);
+		Ok(())
+	}
+
+	pub fn insert_frame(&mut self, index: usize, frame: Frame) -> Result<(), Error> {
+		if index > self.frames.len() {
+			return Err(Error::FrameNotFound(index));
+		}
+
+		Header::check_count(self.frames.len() + 1)?;
+		self.frames.insert(index, frame);
+		Ok(())
+	}
+
+	pub fn remove_frame(&mut self, index: usize) -> Result<Frame, Error> {
+		if index >= self.frames.len() {
+			return Err(Error::FrameNotFound(index));
+		}
+
+		Ok(self.frames.remove(index))
+	}
+
+	/// Decodes frame `index` with `options` in place of the frame's own nibble
+	/// order, alpha mode and `TEXA` override. Start from
+	/// [`Frame::decode_options`] to keep those.
+	pub fn decode_frame(&self, index: usize, options: &DecodeOptions) -> Result<PixelBuffer, Error> {
+		let frame = self.frames.get(index).ok_or(Error::FrameNotFound(index))?;
+
+		frame.decode(options)
+	}
+}
+
+/// A TIM2 image that only parses headers and borrows the picture data from
+/// its buffer. Frames are decoded on demand with [`FrameRef::decode`].
+///
+/// # Examples
+///
+/// ```
+/// fn main() {
+///     let buffer = std::fs::read("../assets/test.tm2").unwrap();
+///     let image = tim2::ImageRef::new(&buffer).unwrap();
+///
+///     for frame in image.frames() {
+///         println!("frame: <{}  {}> {} bytes", frame.width(), frame.height(), frame.data().len());
+///     }
+///
+///     let frame = image.get_frame(0).decode().unwrap();
+///     assert_eq!(frame.width(), 64);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ImageRef<'a> {
+	header: Header,
+	frames: Vec::<FrameRef<'a>>,
+}
+
+impl<'a> ImageRef<'a> {
+	pub fn new(buffer: &'a [u8]) -> Result<ImageRef<'a>, Error> {
+		ImageRef::with_limits(buffer, &Limits::default())
+	}
+
+	/// Parses `buffer` like [`new`](ImageRef::new), with `limits` also applied
+	/// when frames are decoded.
+	pub fn with_limits(buffer: &'a [u8], limits: &Limits) -> Result<ImageRef<'a>, Error> {
+		let mut offset = 0usize;
+		let header = Header::read(buffer, &mut offset)?;
+		let alignment = header.alignment.size();
+
+		limits.check_pictures(header.count)?;
+		let mut frames = Vec::with_capacity(header.count);
+
+		offset = align(offset, alignment);
+		for index in 0..header.count {
+			let frame = FrameRef::read(buffer, &mut offset, index, limits);
+
+			frames.push(frame.map_err(|err| err.in_picture(index, offset))?);
+			offset = align(offset, alignment);
+		}
+
+		Ok(ImageRef { header, frames })
+	}
+
+	pub fn version(&self) -> u8 {
+		self.header.version
+	}
+
+	pub fn alignment(&self) -> Alignment {
+		self.header.alignment
+	}
+
+	pub fn frames(&self) -> &[FrameRef<'a>] {
+		&self.frames
+	}
+
+	pub fn get_frame(&self, index: usize) -> &FrameRef<'a> {
+		&self.frames[index]
+	}
+}
+
+/// A picture inside an [`ImageRef`]: its parsed header and the bytes it spans.
+#[derive(Debug)]
+pub struct FrameRef<'a> {
+	info: FrameInfo,
+	index: usize,
+	offset: usize,
+	bytes: &'a [u8],
+	limits: Limits,
+}
+
+impl<'a> FrameRef<'a> {
+	fn read(buffer: &'a [u8], offset: &mut usize, index: usize, limits: &Limits) -> Result<FrameRef<'a>, Error> {
+		let start = *offset;
+		let info = FrameInfo::read(buffer, offset)?;
+
+		get_slice(buffer, offset, info.data_size())?;
+		Ok(FrameRef { info, index, offset: start, bytes: &buffer[start..*offset], limits: *limits })
+	}
+
+	pub fn decode(&self) -> Result<Frame, Error> {
+		let mut offset = 0usize;
+
+		self.limits.check_picture(&self.info).map_err(|err| err.in_picture(self.index, self.offset))?;
+
+		Frame::read(self.bytes, &mut offset).map_err(|err| err.in_picture(self.index, self.offset + offset))
+	}
+
+	/// Decodes a rectangle of the level the options select directly from the
+	/// stored data, looking each texel up through the swizzle layout when the
+	/// options ask for one.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::{DecodeOptions, SwizzleMode};
+	///
+	/// fn main() {
+	///     let buffer = std::fs::read("../assets/test.tm2").unwrap();
+	///     let image = tim2::ImageRef::new(&buffer).unwrap();
+	///     let frame = image.get_frame(0);
+	///
+	///     for options in [DecodeOptions::new(), DecodeOptions::new().swizzle(SwizzleMode::ForceOn)] {
+	///         let cell = frame.decode_region(16, 8, 8, 4, &options).unwrap();
+	///         assert_eq!(cell, frame.decode().unwrap().decode_region(16, 8, 8, 4, &options).unwrap());
+	///     }
+	///
+	///     assert!(frame.decode_region(0, 0, 1, 1, &DecodeOptions::new().mip_level(1)).is_err());
+	/// }
+	/// ```
+	pub fn decode_region(&self, x: usize, y: usize, width: usize, height: usize, options: &DecodeOptions) -> Result<PixelBuffer, Error> {
+		let level = options.mip_level;
+		if level >= self.info.level_count() {
+			return Err(Error::MipmapNotFound(level));
+		}
+
+		self.info.check_region(level, x, y, width, height)?;
+
+		let palettes = Frame::read_palettes(self.palette_data(), &mut 0, &self.info)?;
+		let palette = Frame::select_palette(&self.info, &palettes, options)?;
+		let resolver = Resolver::new(&self.info, palette.as_deref(), options);
+		let (level_width, level_height) = self.info.level_dimensions(level);
+		let map = if options.swizzle.resolve(false) {
+			Some(SwizzleMap::new(self.info.psm(), level_width, level_height))
+		} else {
+			None
+		};
+
+		/* the levels are stored one after another */
+		let start: usize = (0..level).map(|v| self.info.level_size(v)).sum();
+		let data = &self.data()[start..start + self.info.level_size(level)];
+		let mut result = Vec::with_capacity(width * height);
+
+		for row in y..y + height {
+			for column in x..x + width {
+				let index = match &map {
+					Some(map) => map.index(column, row).ok_or(Error::InvalidRange(column))?,
+					None => row * level_width + column,
+				};
+
+				result.push(resolver.resolve_raw(&self.info, data, index)?);
+			}
+		}
+
+		Ok(result)
+	}
+
+	pub fn info(&self) -> &FrameInfo {
+		&self.info
+	}
+
+	pub fn width(&self) -> usize {
+		self.info.width()
+	}
+
+	pub fn height(&self) -> usize {
+		self.info.height()
+	}
+
+	/// Returns the offset of the picture header from the start of the file.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/// Returns the whole picture block, header included.
+	pub fn bytes(&self) -> &'a [u8] {
+		self.bytes
+	}
+
+	/// Returns the raw image data of every mipmap level, as stored.
+	pub fn data(&self) -> &'a [u8] {
+		let start = self.info.header_size() as usize;
+		let end = self.bytes.len() - self.info.palette_size() as usize;
+
+		&self.bytes[start..end]
+	}
+
+	/// Returns the raw CLUT data, as stored.
+	pub fn palette_data(&self) -> &'a [u8] {
+		&self.bytes[self.bytes.len() - self.info.palette_size() as usize..]
+	}
+}
+
+/// Reads a TIM2 file picture by picture from a stream, starting at its
+/// current position. Picture headers can be inspected before deciding
+/// whether to decode or skip the data that follows them.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+///
+/// fn main() {
+///     let file = File::open("../assets/test.tm2").unwrap();
+///     let mut reader = tim2::Reader::new(file).unwrap();
+///
+///     while let Some(info) = reader.next_info().unwrap() {
+///         if info.width() > 1024 {
+///             reader.skip_frame().unwrap();
+///         } else {
+///             let frame = reader.read_frame().unwrap().unwrap();
+///             println!("frame: <{}  {}>", frame.width(), frame.height());
+///         }
+///     }
+/// }
+/// ```
+pub struct Reader<R: Read + Seek> {
+	reader: R,
+	header: Header,
+	start: u64,
+	index: usize,
+	pending: Option<(FrameInfo, Vec::<u8>, usize)>,
+	limits: Limits,
+	total: usize,
+	end: u64,
+}
+
+impl<R: Read + Seek> Reader<R> {
+	pub fn new(reader: R) -> Result<Reader<R>, Error> {
+		Reader::with_limits(reader, &Limits::default())
+	}
+
+	pub fn with_limits(mut reader: R, limits: &Limits) -> Result<Reader<R>, Error> {
+		let start = reader.stream_position()?;
+		let mut buffer = [0u8; HEADER_SIZE];
+
+		/* skipped pictures are never read, so their data is checked against the stream length */
+		let end = reader.seek(SeekFrom::End(0))?;
+		reader.seek(SeekFrom::Start(start))?;
+
+		reader.read_exact(&mut buffer)?;
+		let header = Header::read(&buffer, &mut 0)?;
+		limits.check_pictures(header.count)?;
+
+		let mut result = Reader {
+			reader,
+			header,
+			start,
+			index: 0,
+			pending: None,
+			limits: *limits,
+			total: 0,
+			end,
+		};
+
+		result.align()?;
+
+		Ok(result)
+	}
+
+	pub fn version(&self) -> u8 {
+		self.header.version
+	}
+
+	pub fn alignment(&self) -> Alignment {
+		self.header.alignment
+	}
+
+	pub fn count(&self) -> usize {
+		self.header.count
+	}
+
+	/// Returns the position of the stream relative to the start of the image.
+	pub fn offset(&mut self) -> Result<usize, Error> {
+		Ok((self.reader.stream_position()? - self.start) as usize)
+	}
+
+	/// Reads the header of the next picture, or returns `None` after the last one.
+	pub fn next_info(&mut self) -> Result<Option<&FrameInfo>, Error> {
+		if self.pending.is_none() && self.index < self.header.count {
+			let start = self.offset()?;
+			let mut buffer = Vec::with_capacity(PICTURE_HEADER_SIZE);
+			let mut offset = 0usize;
+
+			self.read_into(&mut buffer, PICTURE_HEADER_SIZE)?;
+
+			/* the header size decides how much user area follows the fixed part */
+			let header_size = LittleEndian::read_u16(&buffer[12..14]) as usize;
+			self.read_into(&mut buffer, header_size.saturating_sub(PICTURE_HEADER_SIZE))?;
+
+			let info = FrameInfo::read(&buffer, &mut offset)
+				.map_err(|err| err.in_picture(self.index, start + offset))?;
+
+			self.pending = Some((info, buffer, start));
+		}
+
+		Ok(self.pending.as_ref().map(|(info, _, _)| info))
+	}
+
+	/// Reads and decodes the next picture, or returns `None` after the last one.
+	pub fn read_frame(&mut self) -> Result<Option<Frame>, Error> {
+		if self.next_info()?.is_none() {
+			return Ok(None);
+		}
+
+		/* a picture over the limits stays pending, so it can still be skipped */
+		if let Some((info, _, start)) = &self.pending {
+			let bytes = self.limits.check_picture(info).map_err(|err| err.in_picture(self.index, *start))?;
+			let total = self.total.saturating_add(bytes);
+
+			self.limits.check_alloc(total).map_err(|err| err.in_picture(self.index, *start))?;
+			self.total = total;
+		}
+
+		let (info, mut buffer, start) = self.pending.take().unwrap();
+		let mut offset = 0usize;
+
+		self.read_into(&mut buffer, info.data_size())?;
+
+		let frame = Frame::read(&buffer, &mut offset)
+			.map_err(|err| err.in_picture(self.index, start + offset))?;
+
+		self.advance()?;
+
+		Ok(Some(frame))
+	}
+
+	/// Seeks past the next picture without reading its data, returning its header.
+	pub fn skip_frame(&mut self) -> Result<Option<FrameInfo>, Error> {
+		if self.next_info()?.is_none() {
+			return Ok(None);
+		}
+
+		let offset = self.offset()?;
+		let size = self.pending.as_ref().map_or(0, |(info, _, _)| info.data_size());
+		let available = self.end.saturating_sub(self.start + offset as u64) as usize;
+
+		if available < size {
+			let err = Error::UnexpectedEof { offset, needed: size };
+
+			return Err(err.in_picture(self.index, offset + available));
+		}
+
+		let (info, _, _) = self.pending.take().unwrap();
+		self.reader.seek(SeekFrom::Current(size as i64))?;
+		self.advance()?;
+
+		Ok(Some(info))
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	fn read_into(&mut self, buffer: &mut Vec::<u8>, size: usize) -> Result<(), Error> {
+		let offset = self.offset()?;
+		let read = (&mut self.reader).take(size as u64).read_to_end(buffer)
+			.map_err(|err| Error::from(err).in_picture(self.index, offset))?;
+
+		if read < size {
+			let err = Error::UnexpectedEof { offset, needed: size };
+
+			return Err(err.in_picture(self.index, offset + read));
+		}
+
+		Ok(())
+	}
+
+	fn advance(&mut self) -> Result<(), Error> {
+		self.index += 1;
+		self.align()
+	}
+
+	fn align(&mut self) -> Result<(), Error> {
+		let offset = self.offset()?;
+		let aligned = align(offset, self.header.alignment.size());
+
+		self.reader.seek(SeekFrom::Start(self.start + aligned as u64))?;
+		Ok(())
+	}
+}
+
+/// Reads a TIM2 image from a stream, starting at its current position. Only
+/// the bytes that belong to the image are read.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Cursor, Seek, SeekFrom};
+///
+/// fn main() {
+///     /* an image embedded at an offset inside a larger blob */
+///     let mut blob = vec![0u8; 100];
+///     blob.extend(std::fs::read("../assets/test.tm2").unwrap());
+///
+///     let mut cursor = Cursor::new(blob);
+///     cursor.seek(SeekFrom::Start(100)).unwrap();
+///
+///     let image = tim2::read_from(&mut cursor).unwrap();
+///     assert_eq!(image.frames().len(), 1);
+/// }
+/// ```
+pub fn read_from<R: Read + Seek>(reader: R) -> Result<Image, Error> {
+	read_from_with_limits(reader, &Limits::default())
+}
+
+/// Reads a TIM2 image from a stream like [`read_from`], with custom limits.
+pub fn read_from_with_limits<R: Read + Seek>(reader: R, limits: &Limits) -> Result<Image, Error> {
+	let mut reader = Reader::with_limits(reader, limits)?;
+	let mut frames = Vec::with_capacity(reader.count());
+
+	while let Some(frame) = reader.read_frame()? {
+		frames.push(frame);
+	}
+
+	Ok(Image { header: reader.header, frames })
 }
 
 /// Loads a TIM2 image file into memory from buffer.
@@ -81,9 +669,14 @@ impl Image {
 /// }
 /// ```
 pub fn from_buffer(buffer: &[u8]) -> Result<Image, Error> {
+	from_buffer_with_limits(buffer, &Limits::default())
+}
+
+/// Loads a TIM2 image from buffer like [`from_buffer`], with custom limits.
+pub fn from_buffer_with_limits(buffer: &[u8], limits: &Limits) -> Result<Image, Error> {
 	let mut offset = 0usize;
 
-	Image::read(&buffer, &mut offset)
+	Image::read(buffer, &mut offset, limits)
 }
 
 /// Loads a TIM2 image file into memory.
@@ -101,10 +694,31 @@ pub fn from_buffer(buffer: &[u8]) -> Result<Image, Error> {
 /// }
 /// ```
 pub fn load<P: AsRef<Path>>(path: P) -> Result<Image, Error> {
-	let mut offset = 0usize;
-	let mut buffer = Vec::new();
-	let mut file = File::open(path)?;
+	let file = File::open(path)?;
+
+	read_from(BufReader::new(file))
+}
+
+/// Writes a TIM2 image to a file.
+///
+/// # Examples
+///
+/// ```
+/// fn main() {
+///     let image = tim2::load("../assets/test.tm2").unwrap();
+///     let path = std::env::temp_dir().join("tim2_save.tm2");
+/// 
+///     tim2::save(&path, &image).unwrap();
+/// 
+///     let copy = tim2::load(&path).unwrap();
+///     assert_eq!(copy.frames().len(), image.frames().len());
+/// }
+/// ```
+pub fn save<P: AsRef<Path>>(path: P, image: &Image) -> Result<(), Error> {
+	let mut file = BufWriter::new(File::create(path)?);
+
+	image.write(&mut file)?;
+	file.flush()?;
 
-	file.read_to_end(&mut buffer)?;
-	Image::read(&buffer, &mut offset)
+	Ok(())
 }
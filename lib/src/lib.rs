@@ -17,10 +17,27 @@
 mod common;
 mod error;
 mod frame;
+mod gs;
 mod image;
+mod limits;
+mod options;
 mod pixel;
+mod probe;
+mod swizzle;
+mod user_data;
+mod validate;
 
 pub use error::*;
 pub use frame::*;
+pub use gs::*;
 pub use image::*;
+pub use limits::*;
+pub use options::*;
 pub use pixel::*;
+pub use probe::*;
+pub use swizzle::*;
+pub use user_data::*;
+pub use validate::*;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,101 @@
+use crate::error::Error;
+use crate::frame::FrameInfo;
+use crate::gs::Psm;
+use crate::pixel::Pixel;
+
+use std::mem;
+
+/// Bounds checked against header fields before anything is allocated for
+/// them. The defaults are generous for real PS2 textures while keeping a
+/// hostile file from asking for gigabytes.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::{Error, Limits};
+///
+/// fn main() {
+///     let buffer = std::fs::read("../assets/test.tm2").unwrap();
+///     let limits = Limits { max_width: 32, ..Limits::default() };
+///
+///     match tim2::from_buffer_with_limits(&buffer, &limits) {
+///         Err(Error::Picture { source, .. }) => assert!(matches!(*source, Error::LimitExceeded { .. })),
+///         _ => unreachable!(),
+///     }
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+	pub max_pictures: usize,
+	pub max_width: usize,
+	pub max_height: usize,
+	pub max_alloc_bytes: usize,
+}
+
+impl Limits {
+	pub fn unlimited() -> Limits {
+		Limits {
+			max_pictures: usize::MAX,
+			max_width: usize::MAX,
+			max_height: usize::MAX,
+			max_alloc_bytes: usize::MAX,
+		}
+	}
+
+	fn check(name: &'static str, value: usize, limit: usize) -> Result<(), Error> {
+		if value > limit {
+			Err(Error::LimitExceeded { name, value, limit })
+		} else {
+			Ok(())
+		}
+	}
+
+	pub(crate) fn check_pictures(&self, count: usize) -> Result<(), Error> {
+		Limits::check("picture count", count, self.max_pictures)
+	}
+
+	pub(crate) fn check_alloc(&self, bytes: usize) -> Result<(), Error> {
+		Limits::check("allocation size", bytes, self.max_alloc_bytes)
+	}
+
+	/// Checks the picture's dimensions and returns roughly how many bytes
+	/// reading and decoding it will allocate.
+	pub(crate) fn check_picture(&self, info: &FrameInfo) -> Result<usize, Error> {
+		Limits::check("width", info.width(), self.max_width)?;
+		Limits::check("height", info.height(), self.max_height)?;
+
+		/* the stored data is read as is, so its size rather than the dimensions sets what it expands to */
+		let expanded = |size: usize| match info.psm() {
+			Psm::Psmt4 => size.saturating_mul(2),
+			Psm::Psmt8 => size,
+			/* an index per word, and the words themselves to write back */
+			Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh => size / 4 + size,
+			_ => (size / (info.bpp() as usize / 8).max(1)).saturating_mul(mem::size_of::<Pixel>()),
+		};
+
+		let texels = (0..info.level_count())
+			.fold(0usize, |total, level| total.saturating_add(expanded(info.level_size(level))));
+
+		let clut_size = info.clut_count()
+			.saturating_mul(info.color_entry_count() as usize)
+			.saturating_mul(mem::size_of::<Pixel>());
+
+		let bytes = texels
+			.saturating_add(clut_size)
+			.saturating_add(info.data_size());
+
+		self.check_alloc(bytes)?;
+		Ok(bytes)
+	}
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Limits {
+			max_pictures: 4096,
+			max_width: 4096,
+			max_height: 4096,
+			max_alloc_bytes: 256 * 1024 * 1024,
+		}
+	}
+}
@@ -0,0 +1,106 @@
+use crate::frame::NibbleOrder;
+use crate::gs::{ClutStorageMode, GsTexa};
+use crate::pixel::{AlphaMode, Pixel};
+
+/// Whether picture data is read through the GS swizzle layout.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SwizzleMode {
+	/// Follow the frame's [`set_swizzled`](crate::Frame::set_swizzled) setting,
+	/// since TIM2 has no swizzle flag of its own.
+	#[default]
+	Auto,
+	ForceOn,
+	ForceOff,
+}
+
+impl SwizzleMode {
+	pub fn resolve(&self, header_swizzled: bool) -> bool {
+		match self {
+			SwizzleMode::Auto => header_swizzled,
+			SwizzleMode::ForceOn => true,
+			SwizzleMode::ForceOff => false,
+		}
+	}
+}
+
+/// Settings for turning a frame into pixels. The defaults decode the base
+/// level with the first palette, exactly as the header describes it.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::{AlphaMode, DecodeOptions, Pixel};
+///
+/// fn main() {
+///     let image = tim2::load("../assets/test.tm2").unwrap();
+///     let options = DecodeOptions::new()
+///         .alpha_mode(AlphaMode::Ps2Expand)
+///         .color_key(Some(Pixel::from(0, 0, 0, 255)));
+///
+///     let pixels = image.decode_frame(0, &options).unwrap();
+///     assert_eq!(pixels.len(), 64 * 64);
+///
+///     let plain = image.decode_frame(0, &DecodeOptions::default()).unwrap();
+///     assert_eq!(plain, image.get_frame(0).get_pixels().unwrap());
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+	pub palette_index: usize,
+	pub alpha_mode: AlphaMode,
+	pub nibble_order: NibbleOrder,
+	pub swizzle: SwizzleMode,
+	pub clut_storage: Option<ClutStorageMode>,
+	pub texa: Option<GsTexa>,
+	pub mip_level: usize,
+	pub color_key: Option<Pixel>,
+}
+
+impl DecodeOptions {
+	pub fn new() -> DecodeOptions {
+		Default::default()
+	}
+
+	pub fn palette_index(mut self, palette_index: usize) -> Self {
+		self.palette_index = palette_index;
+		self
+	}
+
+	pub fn alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+		self.alpha_mode = alpha_mode;
+		self
+	}
+
+	pub fn nibble_order(mut self, nibble_order: NibbleOrder) -> Self {
+		self.nibble_order = nibble_order;
+		self
+	}
+
+	pub fn swizzle(mut self, swizzle: SwizzleMode) -> Self {
+		self.swizzle = swizzle;
+		self
+	}
+
+	/// Overrides `TEX0.CSM`, which decides whether CLUTs that aren't flagged
+	/// linear are unshuffled from the CSM1 layout or used as stored.
+	pub fn clut_storage(mut self, clut_storage: Option<ClutStorageMode>) -> Self {
+		self.clut_storage = clut_storage;
+		self
+	}
+
+	pub fn texa(mut self, texa: Option<GsTexa>) -> Self {
+		self.texa = texa;
+		self
+	}
+
+	pub fn mip_level(mut self, mip_level: usize) -> Self {
+		self.mip_level = mip_level;
+		self
+	}
+
+	/// Makes pixels equal to `color_key` fully transparent.
+	pub fn color_key(mut self, color_key: Option<Pixel>) -> Self {
+		self.color_key = color_key;
+		self
+	}
+}
@@ -1,14 +1,125 @@
 use crate::error::Error;
+use crate::gs::GsTexa;
 
+use byteorder::{ByteOrder, LittleEndian};
 use std::fmt;
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Format {
 	Indexed4,
 	Indexed8,
 	Abgr1555,
+	Abgr1555S,
 	Rgb888,
 	Rgba8888,
+	Indexed8H,
+	Indexed4HL,
+	Indexed4HH,
+}
+
+/// How the alpha channel of decoded pixels is interpreted. PS2 textures
+/// normally store alpha in the range `0..=0x80`, with `0x80` being opaque.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::AlphaMode;
+///
+/// assert_eq!(AlphaMode::Raw.apply(0x40), 0x40);
+/// assert_eq!(AlphaMode::Ps2Expand.apply(0x40), 0x80);
+/// assert_eq!(AlphaMode::Ps2Expand.apply(0x80), 0xFF);
+/// assert_eq!(AlphaMode::Ps2Expand.apply(0xFF), 0xFF);
+/// assert_eq!(AlphaMode::Opaque.apply(0x00), 0xFF);
+///
+/// /* the inverse maps expanded alpha back to what the file stores */
+/// for a in 0..=0x80 {
+///     assert_eq!(AlphaMode::Ps2Expand.revert(AlphaMode::Ps2Expand.apply(a)), a);
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+	#[default]
+	Raw,
+	Ps2Expand,
+	Opaque,
+}
+
+impl AlphaMode {
+	pub fn apply(&self, alpha: u8) -> u8 {
+		match self {
+			AlphaMode::Raw => alpha,
+			AlphaMode::Ps2Expand => ((alpha as u32 * 255 + 64) / 128).min(255) as u8,
+			AlphaMode::Opaque => 255,
+		}
+	}
+
+	/// Maps alpha decoded with this mode back to its stored value. `Opaque`
+	/// discards the stored alpha, so it is returned unchanged.
+	pub fn revert(&self, alpha: u8) -> u8 {
+		match self {
+			AlphaMode::Raw | AlphaMode::Opaque => alpha,
+			AlphaMode::Ps2Expand => ((alpha as u32 * 128 + 127) / 255) as u8,
+		}
+	}
+}
+
+/// Byte layouts pixels can be decoded into. The premultiplied variants scale
+/// the color channels by alpha.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::{Pixel, PixelLayout};
+///
+/// let pixel = Pixel::from(0x10, 0x20, 0x30, 0x80);
+/// let mut buffer = [0u8; 4];
+///
+/// PixelLayout::Argb8.write(&pixel, &mut buffer);
+/// assert_eq!(buffer, [0x80, 0x10, 0x20, 0x30]);
+///
+/// PixelLayout::Rgba8Premultiplied.write(&pixel, &mut buffer);
+/// assert_eq!(buffer, [0x08, 0x10, 0x18, 0x80]);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelLayout {
+	Rgba8,
+	Bgra8,
+	Rgb8,
+	Argb8,
+	Rgba8Premultiplied,
+	Bgra8Premultiplied,
+	Argb8Premultiplied,
+}
+
+impl PixelLayout {
+	pub fn size(&self) -> usize {
+		match self {
+			PixelLayout::Rgb8 => 3,
+			_ => 4,
+		}
+	}
+
+	pub fn is_premultiplied(&self) -> bool {
+		matches!(self, PixelLayout::Rgba8Premultiplied | PixelLayout::Bgra8Premultiplied | PixelLayout::Argb8Premultiplied)
+	}
+
+	/// Writes `pixel` into the first [`size`](PixelLayout::size) bytes of `buffer`.
+	pub fn write(&self, pixel: &Pixel, buffer: &mut [u8]) {
+		let (r, g, b, a) = if self.is_premultiplied() {
+			let scale = |v: u8| ((v as u32 * pixel.a as u32 + 127) / 255) as u8;
+
+			(scale(pixel.r), scale(pixel.g), scale(pixel.b), pixel.a)
+		} else {
+			(pixel.r, pixel.g, pixel.b, pixel.a)
+		};
+
+		match self {
+			PixelLayout::Rgba8 | PixelLayout::Rgba8Premultiplied => buffer[..4].copy_from_slice(&[r, g, b, a]),
+			PixelLayout::Bgra8 | PixelLayout::Bgra8Premultiplied => buffer[..4].copy_from_slice(&[b, g, r, a]),
+			PixelLayout::Argb8 | PixelLayout::Argb8Premultiplied => buffer[..4].copy_from_slice(&[a, r, g, b]),
+			PixelLayout::Rgb8 => buffer[..3].copy_from_slice(&[r, g, b]),
+		}
+	}
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -28,18 +139,60 @@ impl Pixel {
 		Pixel { r, g, b, a }
 	}
 
+	/// Decodes a 16 bit `ABGR1555` color, expanding each 5 bit channel by bit
+	/// replication so that 0 and 31 map to 0 and 255.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::Pixel;
+	///
+	/// assert_eq!(Pixel::from_abgr1555(0x801F), Pixel::from(255, 0, 0, 255));
+	/// assert_eq!(Pixel::from_abgr1555(0x7C00), Pixel::from(0, 0, 255, 0));
+	/// assert_eq!(Pixel::from_abgr1555(0x0210), Pixel::from(132, 132, 0, 0));
+	///
+	/// /* stored little-endian */
+	/// assert_eq!(Pixel::from_buf(&[0xE0, 0x83]).unwrap(), Pixel::from(0, 255, 0, 255));
+	/// ```
+	pub fn from_abgr1555(raw: u16) -> Pixel {
+		let expand = |v: u16| {
+			let v = (v & 0x1F) as u8;
+
+			(v << 3) | (v >> 2)
+		};
+
+		Pixel {
+			r: expand(raw),
+			g: expand(raw >> 5),
+			b: expand(raw >> 10),
+			a: if raw >> 15 == 1 { 255 } else { 0 },
+		}
+	}
+
+	/// Encodes the pixel as `ABGR1555`, keeping the top 5 bits of each channel
+	/// and setting the alpha bit when `a >= 0x80`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::Pixel;
+	///
+	/// assert_eq!(Pixel::from(255, 0, 0, 255).to_abgr1555(), 0x801F);
+	/// assert_eq!(Pixel::from(0, 0, 255, 0x7F).to_abgr1555(), 0x7C00);
+	/// assert_eq!(Pixel::from(0, 255, 0, 255).to_buf(2).unwrap(), vec![0xE0, 0x83]);
+	/// ```
+	pub fn to_abgr1555(&self) -> u16 {
+		let r = (self.r >> 3) as u16;
+		let g = (self.g >> 3) as u16;
+		let b = (self.b >> 3) as u16;
+		let a = if self.a >= 0x80 { 1 } else { 0 };
+
+		r | (g << 5) | (b << 10) | (a << 15)
+	}
+
 	pub fn from_buf(buf: &[u8]) -> Result<Pixel, Error> {
 		match buf.len() {
-			2 => {
-				let raw = ((buf[0] as u16) << 8) | buf[1] as u16;
-
-				Ok(Pixel {
-					r: ((raw & 0x001F) as f32 / 31.0 * 255.0) as u8,
-					g: (((raw >> 5) & 0x001F) as f32 / 31.0 * 255.0) as u8,
-					b: (((raw >> 10) & 0x001F) as f32 / 31.0 * 255.0) as u8,
-					a: if raw >> 15 == 1 { 255 } else { 0 },
-				})
-			},
+			2 => Ok(Pixel::from_abgr1555(LittleEndian::read_u16(buf))),
 			3 => Ok(Pixel {
 				r: buf[0],
 				g: buf[1],
@@ -56,6 +209,56 @@ impl Pixel {
 		}
 	}
 
+	pub fn to_buf(&self, size: usize) -> Result<Vec::<u8>, Error> {
+		match size {
+			2 => Ok(self.to_abgr1555().to_le_bytes().to_vec()),
+			3 => Ok(vec![self.r, self.g, self.b]),
+			4 => Ok(vec![self.r, self.g, self.b, self.a]),
+			n => Err(Error::InvalidRange(n)),
+		}
+	}
+
+	pub fn with_alpha_mode(&self, mode: AlphaMode) -> Pixel {
+		Pixel { a: mode.apply(self.a), ..*self }
+	}
+
+	pub fn without_alpha_mode(&self, mode: AlphaMode) -> Pixel {
+		Pixel { a: mode.revert(self.a), ..*self }
+	}
+
+	/// Applies the GS `TEXA` alpha expansion to a pixel decoded from `format`.
+	/// Only `Abgr1555` and `Rgb888` pixels are affected.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use tim2::{Format, GsTexa, Pixel};
+	///
+	/// let texa = GsTexa { ta0: 0x00, aem: 0, ta1: 0x80, pabe: 0, fba: 0 };
+	/// let pixel = Pixel::from(255, 0, 0, 255);
+	/// assert_eq!(pixel.with_texa(&texa, Format::Abgr1555).a(), 0x80);
+	///
+	/// let pixel = Pixel::from(255, 0, 0, 0);
+	/// assert_eq!(pixel.with_texa(&texa, Format::Abgr1555).a(), 0x00);
+	///
+	/// /* AEM makes black texels transparent */
+	/// let texa = GsTexa { ta0: 0x80, aem: 1, ta1: 0x80, pabe: 0, fba: 0 };
+	/// assert_eq!(Pixel::from(0, 0, 0, 255).with_texa(&texa, Format::Rgb888).a(), 0);
+	/// assert_eq!(Pixel::from(1, 0, 0, 255).with_texa(&texa, Format::Rgb888).a(), 0x80);
+	/// ```
+	pub fn with_texa(&self, texa: &GsTexa, format: Format) -> Pixel {
+		let black = self.r == 0 && self.g == 0 && self.b == 0;
+		let low_alpha = if texa.aem != 0 && black { 0 } else { texa.ta0 };
+
+		let a = match format {
+			Format::Abgr1555 | Format::Abgr1555S => if self.a >= 0x80 { texa.ta1 } else { low_alpha },
+			Format::Rgb888 => low_alpha,
+			_ => self.a,
+		};
+
+		Pixel { a, ..*self }
+	}
+
 	pub fn r(&self) -> u8 { self.r }
 
 	pub fn g(&self) -> u8 { self.g }
@@ -0,0 +1,109 @@
+use crate::error::Error;
+use crate::frame::FrameInfo;
+use crate::image::{Alignment, Reader};
+use crate::pixel::Format;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::Path;
+
+/// Header level description of a TIM2 file, as returned by [`probe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+	pub version: u8,
+	pub alignment: Alignment,
+	pub pictures: Vec::<PictureInfo>,
+}
+
+/// Header level description of a single picture. Offsets are in bytes from
+/// the start of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureInfo {
+	pub width: usize,
+	pub height: usize,
+	pub format: Option<Format>,
+	pub mipmap_count: usize,
+	pub clut_count: usize,
+	pub offset: usize,
+	pub header_size: usize,
+	pub image_offset: usize,
+	pub image_size: usize,
+	pub palette_offset: usize,
+	pub palette_size: usize,
+}
+
+impl PictureInfo {
+	fn new(info: &FrameInfo, offset: usize) -> PictureInfo {
+		let header_size = info.header_size() as usize;
+		let palette_size = info.palette_size() as usize;
+		let image_size = info.data_size() - palette_size;
+
+		PictureInfo {
+			width: info.width(),
+			height: info.height(),
+			format: info.pixel_format().ok(),
+			mipmap_count: info.level_count(),
+			clut_count: info.clut_count(),
+			offset,
+			header_size,
+			image_offset: offset + header_size,
+			image_size,
+			palette_offset: offset + header_size + image_size,
+			palette_size,
+		}
+	}
+}
+
+fn probe_from<R: Read + Seek>(reader: R) -> Result<ImageInfo, Error> {
+	let mut reader = Reader::new(reader)?;
+	let mut pictures = Vec::with_capacity(reader.count());
+
+	loop {
+		let offset = reader.offset()?;
+
+		match reader.skip_frame()? {
+			Some(info) => pictures.push(PictureInfo::new(&info, offset)),
+			None => break,
+		}
+	}
+
+	Ok(ImageInfo { version: reader.version(), alignment: reader.alignment(), pictures })
+}
+
+/// Reads the file and picture headers of a TIM2 image without decoding any
+/// pixel data.
+///
+/// # Examples
+///
+/// ```
+/// fn main() {
+///     let buffer = std::fs::read("../assets/test.tm2").unwrap();
+///     let info = tim2::probe(&buffer).unwrap();
+///
+///     for picture in &info.pictures {
+///         println!("<{}  {}> {:?} at {:#x}", picture.width, picture.height, picture.format, picture.offset);
+///     }
+/// }
+/// ```
+pub fn probe(buffer: &[u8]) -> Result<ImageInfo, Error> {
+	probe_from(Cursor::new(buffer))
+}
+
+/// Reads the file and picture headers of a TIM2 file, seeking past the
+/// picture data instead of reading it.
+///
+/// # Examples
+///
+/// ```
+/// fn main() {
+///     let info = tim2::probe_path("../assets/test.tm2").unwrap();
+///
+///     assert_eq!(info.pictures.len(), 1);
+///     assert_eq!(info.pictures[0].offset, 16);
+/// }
+/// ```
+pub fn probe_path<P: AsRef<Path>>(path: P) -> Result<ImageInfo, Error> {
+	let file = File::open(path)?;
+
+	probe_from(BufReader::new(file))
+}
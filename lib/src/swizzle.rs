@@ -0,0 +1,254 @@
+//! GS memory swizzling.
+//!
+//! Games commonly upload 4, 8 and 16 bit textures through a 32 bit transfer,
+//! and store them on disk already laid out that way. Reading such data back
+//! means mapping every texel through the GS page, block and column layout of
+//! its own pixel storage mode, then finding where that memory address lands
+//! in the `PSMCT32` image that was written.
+
+use crate::error::Error;
+use crate::gs::Psm;
+
+const PAGE_WORDS: usize = 2048;
+const BLOCK_WORDS: usize = 64;
+const COLUMN_WORDS: usize = 16;
+
+/* block order inside a page for PSMCT32 and PSMT8, indexed by [row][column] */
+const BLOCK_32: [[usize; 8]; 4] = [
+	[ 0,  1,  4,  5, 16, 17, 20, 21],
+	[ 2,  3,  6,  7, 18, 19, 22, 23],
+	[ 8,  9, 12, 13, 24, 25, 28, 29],
+	[10, 11, 14, 15, 26, 27, 30, 31],
+];
+
+/* block order inside a page for PSMCT16 and PSMT4, indexed by [row][column] */
+const BLOCK_16: [[usize; 4]; 8] = [
+	[ 0,  2,  8, 10],
+	[ 1,  3,  9, 11],
+	[ 4,  6, 12, 14],
+	[ 5,  7, 13, 15],
+	[16, 18, 24, 26],
+	[17, 19, 25, 27],
+	[20, 22, 28, 30],
+	[21, 23, 29, 31],
+];
+
+/* block order inside a page for PSMCT16S, indexed by [row][column] */
+const BLOCK_16S: [[usize; 4]; 8] = [
+	[ 0,  2, 16, 18],
+	[ 1,  3, 17, 19],
+	[ 8, 10, 24, 26],
+	[ 9, 11, 25, 27],
+	[ 4,  6, 20, 22],
+	[ 5,  7, 21, 23],
+	[12, 14, 28, 30],
+	[13, 15, 29, 31],
+];
+
+/* word order along the two rows that make up every column stripe */
+const COLUMN_WORD: [[usize; 8]; 2] = [
+	[0, 1, 4, 5,  8,  9, 12, 13],
+	[2, 3, 6, 7, 10, 11, 14, 15],
+];
+
+struct Layout {
+	page_width: usize,
+	page_height: usize,
+	block_width: usize,
+	block_height: usize,
+	nibbles: usize,
+}
+
+fn layout(psm: Psm) -> Layout {
+	match psm {
+		Psm::Psmct32 | Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh => Layout { page_width: 64, page_height: 32, block_width: 8, block_height: 8, nibbles: 8 },
+		Psm::Psmct24 => Layout { page_width: 64, page_height: 32, block_width: 8, block_height: 8, nibbles: 6 },
+		Psm::Psmct16 | Psm::Psmct16s => Layout { page_width: 64, page_height: 64, block_width: 16, block_height: 8, nibbles: 4 },
+		Psm::Psmt8 => Layout { page_width: 128, page_height: 64, block_width: 16, block_height: 16, nibbles: 2 },
+		Psm::Psmt4 => Layout { page_width: 128, page_height: 128, block_width: 32, block_height: 16, nibbles: 1 },
+	}
+}
+
+/// Returns the nibble address of texel `(x, y)` in a buffer `pages_per_row` pages wide.
+fn address(psm: Psm, x: usize, y: usize, pages_per_row: usize) -> usize {
+	let layout = layout(psm);
+	let page = (y / layout.page_height) * pages_per_row + x / layout.page_width;
+	let x = x % layout.page_width;
+	let y = y % layout.page_height;
+
+	let (block, column, word, nibble) = match psm {
+		Psm::Psmct32 | Psm::Psmct24 | Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh => {
+			let block = BLOCK_32[y >> 3][x >> 3];
+			let word = COLUMN_WORD[y & 1][x & 7];
+
+			(block, (y >> 1) & 3, word, 0)
+		},
+		Psm::Psmct16 | Psm::Psmct16s => {
+			let block = if psm == Psm::Psmct16 { BLOCK_16[y >> 3][x >> 4] } else { BLOCK_16S[y >> 3][x >> 4] };
+			let word = COLUMN_WORD[y & 1][x & 7];
+
+			(block, (y >> 1) & 3, word, ((x >> 3) & 1) * 4)
+		},
+		Psm::Psmt8 | Psm::Psmt4 => {
+			let column = (y >> 2) & 3;
+			/* odd columns swap their upper and lower row pairs */
+			let row = (y & 3) ^ ((column & 1) << 1);
+			let word = COLUMN_WORD[row & 1][(x + (row >> 1) * 4) & 7];
+
+			if psm == Psm::Psmt8 {
+				let byte = ((x >> 3) & 1) * 2 + ((y & 3) >> 1);
+
+				(BLOCK_32[y >> 4][x >> 4], column, word, byte * 2)
+			} else {
+				let nibble = ((x >> 3) & 3) * 2 + ((y & 3) >> 1);
+
+				(BLOCK_16[y >> 4][x >> 5], column, word, nibble)
+			}
+		},
+	};
+
+	(page * PAGE_WORDS + block * BLOCK_WORDS + column * COLUMN_WORDS + word) * 8 + nibble
+}
+
+/// Maps linear texel positions to their index in data written through a `PSMCT32` transfer.
+pub struct SwizzleMap {
+	psm: Psm,
+	width: usize,
+	height: usize,
+	pages_per_row: usize,
+	view_width: usize,
+	view_height: usize,
+	page_positions: Vec::<(usize, usize)>,
+}
+
+impl SwizzleMap {
+	pub fn new(psm: Psm, width: usize, height: usize) -> SwizzleMap {
+		let layout = layout(psm);
+		let pages_per_row = width.div_ceil(layout.page_width).max(1);
+		let mut page_positions = vec![(0, 0); PAGE_WORDS];
+
+		for y in 0..32 {
+			for x in 0..64 {
+				page_positions[address(Psm::Psmct32, x, y, 1) / 8] = (x, y);
+			}
+		}
+
+		let mut map = SwizzleMap {
+			psm,
+			width,
+			height,
+			pages_per_row,
+			view_width: 0,
+			view_height: 0,
+			page_positions,
+		};
+
+		/* the PSMCT32 image is the bounding box of every 8x8 block the texture touches */
+		for y in (0..height).step_by(layout.block_height) {
+			for x in (0..width).step_by(layout.block_width) {
+				let (view_x, view_y) = map.view_position(x, y);
+
+				map.view_width = map.view_width.max((view_x & !7) + 8);
+				map.view_height = map.view_height.max((view_y & !7) + 8);
+			}
+		}
+
+		map
+	}
+
+	fn view_position(&self, x: usize, y: usize) -> (usize, usize) {
+		let word = address(self.psm, x, y, self.pages_per_row) / 8;
+		let page = word / PAGE_WORDS;
+		let (page_x, page_y) = self.page_positions[word % PAGE_WORDS];
+
+		((page % self.pages_per_row) * 64 + page_x, (page / self.pages_per_row) * 32 + page_y)
+	}
+
+	/// Returns how many texels long the swizzled data has to be: the whole
+	/// `PSMCT32` bounding box, which can be more than `width * height`.
+	pub fn stored_len(&self) -> usize {
+		match self.psm {
+			Psm::Psmct32 | Psm::Psmct24 | Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh => self.width * self.height,
+			psm => self.view_width * self.view_height * 8 / layout(psm).nibbles,
+		}
+	}
+
+	/// Returns the index of texel `(x, y)` inside the swizzled data.
+	pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+		if x >= self.width || y >= self.height {
+			return None;
+		}
+
+		if let Psm::Psmct32 | Psm::Psmct24 | Psm::Psmt8h | Psm::Psmt4hl | Psm::Psmt4hh = self.psm {
+			return Some(y * self.width + x);
+		}
+
+		let nibbles = layout(self.psm).nibbles;
+		let nibble = address(self.psm, x, y, self.pages_per_row) % 8;
+		let (view_x, view_y) = self.view_position(x, y);
+
+		Some(((view_y * self.view_width + view_x) * 8 + nibble) / nibbles)
+	}
+}
+
+/// Reorders texels written through a `PSMCT32` transfer into linear order.
+/// The buffer has to cover the whole swizzled layout, see [`SwizzleMap::stored_len`].
+///
+/// # Examples
+///
+/// ```
+/// use tim2::Psm;
+///
+/// let map = tim2::SwizzleMap::new(Psm::Psmt8, 16, 16);
+/// let stored: Vec<u8> = (0..map.stored_len()).map(|v| v as u8).collect();
+/// let texels = tim2::unswizzle(&stored, 16, 16, Psm::Psmt8).unwrap();
+///
+/// assert_eq!(texels[0], stored[map.index(0, 0).unwrap()]);
+/// ```
+pub fn unswizzle<T: Default + Copy>(buffer: &[T], width: usize, height: usize, psm: Psm) -> Result<Vec::<T>, Error> {
+	let map = SwizzleMap::new(psm, width, height);
+	let needed = map.stored_len();
+	if buffer.len() < needed {
+		return Err(Error::BufferTooSmall { needed, found: buffer.len() });
+	}
+
+	let mut result = vec![Default::default(); buffer.len()];
+
+	for y in 0..height {
+		for x in 0..width {
+			if let (Some(slot), Some(i)) = (result.get_mut(y * width + x), map.index(x, y)) {
+				*slot = buffer[i];
+			}
+		}
+	}
+
+	Ok(result)
+}
+
+/// Reorders linear texels into the layout of a `PSMCT32` transfer, the inverse
+/// of [`unswizzle`]. The result is padded out to the whole swizzled layout.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::Psm;
+///
+/// let texels: Vec<u8> = (0..=255).collect();
+/// let stored = tim2::swizzle(&texels, 16, 16, Psm::Psmt8);
+///
+/// assert_eq!(&tim2::unswizzle(&stored, 16, 16, Psm::Psmt8).unwrap()[..256], &texels[..]);
+/// ```
+pub fn swizzle<T: Default + Copy>(buffer: &[T], width: usize, height: usize, psm: Psm) -> Vec::<T> {
+	let map = SwizzleMap::new(psm, width, height);
+	let mut result = vec![Default::default(); buffer.len().max(map.stored_len())];
+
+	for y in 0..height {
+		for x in 0..width {
+			if let (Some(i), Some(value)) = (map.index(x, y), buffer.get(y * width + x)) {
+				result[i] = *value;
+			}
+		}
+	}
+
+	result
+}
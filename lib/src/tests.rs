@@ -0,0 +1,479 @@
+/* regression tests on hand-built files; the doc examples only show usage */
+
+use crate::*;
+
+const SAMPLE: &[u8] = include_bytes!("../../assets/test.tm2");
+
+/// A picture block, with its sizes worked out from the parts it is given
+/// unless a test overrides them.
+#[derive(Debug, Clone, Default)]
+struct Picture {
+	bpp_id: u8,
+	width: u16,
+	height: u16,
+	clut_format: u8,
+	color_entry_count: u16,
+	tex0: u64,
+	tex_clut: u32,
+	levels: Vec::<u32>,
+	user: Vec::<u8>,
+	image: Vec::<u8>,
+	palette: Vec::<u8>,
+	image_size: Option<u32>,
+	header_size: Option<u16>,
+}
+
+impl Picture {
+	fn new(bpp_id: u8, width: u16, height: u16, image: &[u8]) -> Picture {
+		Picture { bpp_id, width, height, image: image.to_vec(), ..Default::default() }
+	}
+
+	/// Adds a CLUT of 32 bit colors, entry `i` being `(i, 0, 0, 0x80)`.
+	fn ramp(mut self, clut_format: u8, count: u16) -> Picture {
+		self.clut_format = clut_format;
+		self.color_entry_count = count;
+		self.palette = (0..count).flat_map(|i| [i as u8, 0, 0, 0x80]).collect();
+		self
+	}
+
+	fn tex0(mut self, tex0: u64) -> Picture {
+		self.tex0 = tex0;
+		self
+	}
+
+	fn levels(mut self, sizes: &[u32]) -> Picture {
+		self.levels = sizes.to_vec();
+		self
+	}
+
+	fn user(mut self, user: &[u8]) -> Picture {
+		self.user = user.to_vec();
+		self
+	}
+
+	fn bytes(&self) -> Vec::<u8> {
+		let mipmap_size = match self.levels.len() {
+			0 | 1 => 0,
+			n => (16 + n * 4 + 15) & !15,
+		};
+
+		let header_size = self.header_size.unwrap_or((48 + mipmap_size + self.user.len()) as u16);
+		let image_size = self.image_size.unwrap_or(self.image.len() as u32);
+		let total_size = header_size as u32 + image_size + self.palette.len() as u32;
+		let mut buffer = Vec::new();
+
+		buffer.extend_from_slice(&total_size.to_le_bytes());
+		buffer.extend_from_slice(&(self.palette.len() as u32).to_le_bytes());
+		buffer.extend_from_slice(&image_size.to_le_bytes());
+		buffer.extend_from_slice(&header_size.to_le_bytes());
+		buffer.extend_from_slice(&self.color_entry_count.to_le_bytes());
+		buffer.extend_from_slice(&[0, self.levels.len().max(1) as u8, self.clut_format, self.bpp_id]);
+		buffer.extend_from_slice(&self.width.to_le_bytes());
+		buffer.extend_from_slice(&self.height.to_le_bytes());
+		buffer.extend_from_slice(&self.tex0.to_le_bytes());
+		buffer.extend_from_slice(&[0; 12]);
+		buffer.extend_from_slice(&self.tex_clut.to_le_bytes());
+		if mipmap_size > 0 {
+			buffer.extend_from_slice(&[0; 16]);
+			for size in &self.levels {
+				buffer.extend_from_slice(&size.to_le_bytes());
+			}
+
+			buffer.resize(48 + mipmap_size, 0);
+		}
+
+		buffer.extend_from_slice(&self.user);
+		buffer.extend_from_slice(&self.image);
+		buffer.extend_from_slice(&self.palette);
+		buffer
+	}
+}
+
+/// Builds a file with each picture starting on the given alignment.
+fn file(alignment: Alignment, pictures: &[Picture]) -> Vec::<u8> {
+	let align_id = if alignment == Alignment::Align128 { 1 } else { 0 };
+	let mut buffer = b"TIM2\x04".to_vec();
+
+	buffer.push(align_id);
+	buffer.extend_from_slice(&(pictures.len() as u16).to_le_bytes());
+	buffer.extend_from_slice(&[0; 8]);
+	for picture in pictures {
+		buffer.resize(buffer.len().div_ceil(alignment.size()) * alignment.size(), 0);
+		buffer.extend_from_slice(&picture.bytes());
+	}
+
+	buffer
+}
+
+fn single(picture: Picture) -> Vec::<u8> {
+	file(Alignment::Align16, &[picture])
+}
+
+fn written(image: &Image) -> Vec::<u8> {
+	let mut output = Vec::new();
+
+	image.write(&mut output).unwrap();
+	output
+}
+
+fn indices(frame: &Frame) -> Vec::<u8> {
+	match frame.data() {
+		DataKind::Indices(v) => v.clone(),
+		DataKind::Pixels(_) => panic!("expected indices"),
+	}
+}
+
+fn cause(err: Error) -> Error {
+	match err {
+		Error::Picture { source, .. } => *source,
+		err => err,
+	}
+}
+
+fn red(value: u8) -> Pixel {
+	Pixel::from(value, 0, 0, 0x80)
+}
+
+#[test]
+fn nibble_order_swaps_and_writes_back() {
+	let buffer = single(Picture::new(4, 4, 1, &[0x21, 0xF3]).ramp(0x83, 16));
+	let mut image = from_buffer(&buffer).unwrap();
+	assert_eq!(indices(image.get_frame(0)), vec![0x1, 0x2, 0x3, 0xF]);
+
+	image.set_nibble_order(NibbleOrder::HighFirst);
+	assert_eq!(indices(image.get_frame(0)), vec![0x2, 0x1, 0xF, 0x3]);
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn mipmap_levels_decode_at_their_own_size() {
+	let image: Vec::<u8> = (0..20).flat_map(|i| [i, 0, 0, 255]).collect();
+	let buffer = single(Picture::new(3, 4, 4, &image).levels(&[64, 16]));
+	let image = from_buffer(&buffer).unwrap();
+	let frame = image.get_frame(0);
+	let level = &frame.mipmaps()[0];
+
+	assert_eq!(frame.info().level_count(), 2);
+	assert_eq!((level.width(), level.height()), (2, 2));
+	assert_eq!(level.data().len(), 4);
+	assert_eq!(frame.get_pixels().unwrap()[15], Pixel::from(15, 0, 0, 255));
+	assert_eq!(frame.get_mipmap_pixels(0).unwrap()[3], Pixel::from(19, 0, 0, 255));
+	assert!(matches!(frame.get_mipmap_pixels(1), Err(Error::MipmapNotFound(1))));
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn swizzled_levels_decode_to_their_own_size() {
+	/* a 16x16 PSMT4 texture spans a whole 32x16 block when swizzled */
+	let buffer = single(Picture::new(4, 16, 16, &[0x21; 256]).ramp(0x83, 16));
+	let mut image = from_buffer(&buffer).unwrap();
+	image.set_swizzled(true).unwrap();
+
+	let frame = image.get_frame(0);
+	assert_eq!(frame.get_pixels().unwrap().len(), 256);
+	assert_eq!(frame.to_raw(None).unwrap().len(), 256 * 4);
+	assert_eq!(frame.decode(&DecodeOptions::new().swizzle(SwizzleMode::ForceOff)).unwrap().len(), 256);
+
+	let image = from_buffer(&buffer).unwrap();
+	let frame = image.get_frame(0);
+	assert_eq!(frame.decode(&DecodeOptions::new().swizzle(SwizzleMode::ForceOn)).unwrap().len(), 256);
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn mipmap_sizes_must_add_up_to_image_size() {
+	let mut picture = Picture::new(3, 4, 4, &[0; 80]).levels(&[64, 16]);
+	picture.image_size = Some(96);
+
+	let err = cause(from_buffer(&single(picture)).unwrap_err());
+	assert!(matches!(err, Error::MipmapSizeMismatch { image_size: 96, levels: 80 }));
+}
+
+#[test]
+fn user_data_writes_back_byte_for_byte() {
+	/* a Shift-JIS comment, then a NUL and a tool's own bytes */
+	let mut user = b"eXt\0\x20\0\0\0\x02\0\0\0\0\0\0\0\xAA\xBB".to_vec();
+	user.extend_from_slice(&[0x83, 0x65, 0x83, 0x58, 0x83, 0x67, 0, 9, 9, 9, 9, 9, 9, 9]);
+
+	let buffer = single(Picture::new(3, 1, 1, &[1, 2, 3, 4]).user(&user));
+	let image = from_buffer(&buffer).unwrap();
+	let user_data = image.get_frame(0).info().user_data();
+
+	assert_eq!(user_data.raw, vec![0xAA, 0xBB]);
+	assert_eq!(user_data.comment_bytes, vec![0x83, 0x65, 0x83, 0x58, 0x83, 0x67]);
+	assert_eq!(user_data.trailing, vec![0, 9, 9, 9, 9, 9, 9, 9]);
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn user_data_comment_can_fill_the_area() {
+	let user = b"eXt\0\x20\0\0\0\0\0\0\0\0\0\0\0made with a tool";
+	let buffer = single(Picture::new(3, 1, 1, &[1, 2, 3, 4]).user(user));
+	let image = from_buffer(&buffer).unwrap();
+
+	assert_eq!(image.get_frame(0).info().user_data().comment().unwrap(), "made with a tool");
+	assert_eq!(image.get_frame(0).get_pixels().unwrap()[0], Pixel::from(1, 2, 3, 4));
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn csa_selects_a_window_of_the_clut() {
+	let buffer = single(Picture::new(4, 2, 1, &[0x10]).ramp(0x83, 32).tex0(1 << 56));
+	let image = from_buffer(&buffer).unwrap();
+	let frame = image.get_frame(0);
+
+	assert_eq!(frame.info().clut_offset(), 16);
+	assert_eq!(frame.get_pixels().unwrap(), vec![red(16), red(17)]);
+}
+
+#[test]
+fn csa_applies_to_high_nibble_modes() {
+	/* PSMT4HL keeps its index in the low nibble of each top byte */
+	let picture = Picture::new(3, 2, 1, &[0, 0, 0, 0xA1, 0, 0, 0, 0xB2]).ramp(0x83, 32).tex0(0x24 << 20 | 1 << 56);
+	let image = from_buffer(&single(picture)).unwrap();
+
+	assert_eq!(image.get_frame(0).get_pixels().unwrap(), vec![red(17), red(18)]);
+}
+
+#[test]
+fn csm2_clut_is_used_as_stored() {
+	let image: Vec::<u8> = (0..=255).collect();
+	let buffer = single(Picture::new(5, 16, 16, &image).ramp(0x03, 256).tex0(0x13 << 20 | 1 << 55));
+	let image = from_buffer(&buffer).unwrap();
+	let frame = image.get_frame(0);
+
+	assert_eq!(frame.info().clut_storage_mode(), ClutStorageMode::Csm2);
+	assert!(frame.get_pixels().unwrap().iter().enumerate().all(|(i, v)| *v == red(i as u8)));
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn high_indexed_modes_keep_the_rest_of_each_word() {
+	let words = [0x11, 0x22, 0x33, 0x07, 0x00, 0x00, 0x00, 0xFE];
+	let buffer = single(Picture::new(3, 2, 1, &words).ramp(0x83, 256).tex0(0x1B << 20));
+	let image = from_buffer(&buffer).unwrap();
+
+	assert_eq!(image.get_frame(0).format().unwrap(), Format::Indexed8H);
+	assert_eq!(indices(image.get_frame(0)), vec![0x07, 0xFE]);
+	assert_eq!(written(&image), buffer);
+
+	/* PSMT4HH only owns the top nibble */
+	let buffer = single(Picture::new(3, 2, 1, &words).ramp(0x83, 16).tex0(0x2C << 20));
+	let mut image = from_buffer(&buffer).unwrap();
+	assert_eq!(image.get_frame(0).format().unwrap(), Format::Indexed4HH);
+
+	image.frames_mut()[0].set_index(1, 0, 0x3).unwrap();
+	assert_eq!(written(&image)[64..72], [0x11, 0x22, 0x33, 0x07, 0x00, 0x00, 0x00, 0x3E]);
+}
+
+#[test]
+fn editing_past_short_data_is_an_error() {
+	/* 4x4 texels announced, but only one row stored */
+	let mut image = from_buffer(&single(Picture::new(3, 4, 4, &[0; 16]))).unwrap();
+	let frame = &mut image.frames_mut()[0];
+
+	frame.set_pixel(3, 0, red(1)).unwrap();
+	assert!(matches!(frame.set_pixel(0, 3, red(1)), Err(Error::InvalidRange(12))));
+
+	let mut image = from_buffer(&single(Picture::new(5, 4, 4, &[0; 4]).ramp(0x83, 16))).unwrap();
+	let frame = &mut image.frames_mut()[0];
+
+	frame.set_index(3, 0, 1).unwrap();
+	assert!(matches!(frame.set_index(0, 1, 1), Err(Error::InvalidRange(4))));
+}
+
+#[test]
+fn errors_name_the_picture_and_offset() {
+	let err = from_buffer(&single(Picture::new(7, 1, 1, &[0; 4]))).unwrap_err();
+	assert_eq!(err.to_string(), "picture 0 @0x24: unsupported bpp format 7");
+}
+
+#[test]
+fn truncated_data_is_an_error() {
+	let err = cause(from_buffer(&SAMPLE[..1000]).unwrap_err());
+	assert!(matches!(err, Error::UnexpectedEof { offset: 64, needed: 4096 }));
+}
+
+#[test]
+fn probing_truncated_data_is_an_error() {
+	let err = cause(probe(&SAMPLE[..200]).unwrap_err());
+	assert!(matches!(err, Error::UnexpectedEof { offset: 64, needed: 5120 }));
+	assert!(probe(SAMPLE).is_ok());
+}
+
+#[test]
+fn short_header_size_is_an_error() {
+	let mut picture = Picture::new(3, 1, 1, &[0; 4]);
+	picture.header_size = Some(40);
+
+	let err = cause(from_buffer(&single(picture)).unwrap_err());
+	assert!(matches!(err, Error::InvalidHeaderSize(40)));
+}
+
+#[test]
+fn indexed_picture_without_palette_is_an_error() {
+	for bpp_id in [4, 5] {
+		let err = cause(from_buffer(&single(Picture::new(bpp_id, 2, 2, &[0; 4]))).unwrap_err());
+		assert!(matches!(err, Error::InvalidPaletteSize(0)));
+	}
+}
+
+#[test]
+fn index_past_the_clut_is_an_error() {
+	let image = from_buffer(&single(Picture::new(5, 2, 1, &[1, 27]).ramp(0x83, 16))).unwrap();
+
+	let err = image.get_frame(0).get_pixels().unwrap_err();
+	assert!(matches!(err, Error::PaletteIndexOutOfRange(27)));
+}
+
+#[test]
+fn pictures_follow_128_byte_alignment() {
+	let picture = Picture::new(3, 2, 2, &[7; 16]);
+	let buffer = file(Alignment::Align128, &[picture.clone(), picture]);
+	let image = from_buffer(&buffer).unwrap();
+
+	assert_eq!(image.alignment(), Alignment::Align128);
+	assert_eq!(image.frames().len(), 2);
+	assert_eq!(image.get_frame(1).get_pixels().unwrap(), image.get_frame(0).get_pixels().unwrap());
+	assert_eq!(written(&image), buffer);
+}
+
+#[test]
+fn abgr1555_round_trips() {
+	for raw in 0..=u16::MAX {
+		assert_eq!(Pixel::from_abgr1555(raw).to_abgr1555(), raw);
+	}
+}
+
+#[test]
+fn overflowing_stride_is_an_error() {
+	let image = from_buffer(SAMPLE).unwrap();
+	let frame = image.get_frame(0);
+	let mut buffer = vec![0u8; 64 * 64 * 4];
+
+	let err = frame.decode_into(&mut buffer, PixelLayout::Rgba8, usize::MAX / 2, &frame.decode_options()).unwrap_err();
+	assert!(matches!(err, Error::InvalidStride(_)));
+}
+
+/* the usual PSMT8 unswizzle, for a texture as wide as its transfer */
+fn reference_unswizzle8(buffer: &[u8], width: usize, height: usize) -> Vec::<u8> {
+	let mut result = vec![0; width * height];
+
+	for y in 0..height {
+		for x in 0..width {
+			let block = (y & !0xF) * width + (x & !0xF) * 2;
+			let swap = (((y + 2) >> 2) & 1) * 4;
+			let row = ((((y & !3) >> 1) + (y & 1)) & 7) * width * 2;
+			let column = ((x + swap) & 7) * 4;
+			let byte = ((y >> 1) & 1) + ((x >> 2) & 2);
+
+			result[y * width + x] = buffer[block + row + column + byte];
+		}
+	}
+
+	result
+}
+
+#[test]
+fn psmt8_unswizzle_matches_the_reference() {
+	let stored: Vec::<u8> = (0..128 * 64).map(|v| (v * 7 % 251) as u8).collect();
+
+	assert_eq!(unswizzle(&stored, 128, 64, Psm::Psmt8).unwrap(), reference_unswizzle8(&stored, 128, 64));
+}
+
+#[test]
+fn psmct16_pairs_texels_eight_apart() {
+	let stored: Vec::<u16> = (0..64 * 64).collect();
+	let texels = unswizzle(&stored, 64, 64, Psm::Psmct16).unwrap();
+
+	assert_eq!(&texels[..10], &[0, 2, 4, 6, 8, 10, 12, 14, 1, 3]);
+}
+
+#[test]
+fn psmt4_block_fills_a_psmct32_block() {
+	let map = SwizzleMap::new(Psm::Psmt4, 128, 128);
+	assert_eq!(map.stored_len(), 128 * 128);
+
+	for y in 0..16 {
+		for x in 0..32 {
+			let word = map.index(x, y).unwrap() / 8;
+			assert!(word % 64 < 8 && word / 64 < 8);
+		}
+	}
+}
+
+#[test]
+fn short_swizzled_data_is_an_error() {
+	/* a 16x16 PSMT4 texture still spans a whole PSMCT32 block */
+	let err = unswizzle(&[0u8; 256], 16, 16, Psm::Psmt4).unwrap_err();
+	assert!(matches!(err, Error::BufferTooSmall { needed: 512, found: 256 }));
+}
+
+#[test]
+fn swizzle_round_trips_every_psm() {
+	let formats = [Psm::Psmct32, Psm::Psmct24, Psm::Psmct16, Psm::Psmct16s, Psm::Psmt8, Psm::Psmt4, Psm::Psmt8h];
+
+	for psm in formats {
+		for (width, height) in [(8, 8), (16, 16), (32, 8), (64, 32), (128, 128), (256, 64)] {
+			let texels: Vec::<u32> = (1..=(width * height) as u32).collect();
+			let stored = swizzle(&texels, width, height, psm);
+
+			/* every texel lands somewhere, and nowhere twice */
+			assert_eq!(stored.iter().filter(|v| **v != 0).count(), texels.len());
+
+			let back = unswizzle(&stored, width, height, psm).unwrap();
+			assert_eq!(&back[..texels.len()], &texels[..], "{:?} {}x{}", psm, width, height);
+		}
+	}
+}
+
+#[test]
+fn limits_follow_the_stored_data() {
+	/* a single 16 bit texel followed by a megabyte that still has to be read as colors */
+	let buffer = single(Picture::new(1, 1, 1, &vec![0; 1 << 20]));
+	let limits = Limits { max_alloc_bytes: 5 << 19, ..Limits::default() };
+
+	let err = cause(from_buffer_with_limits(&buffer, &limits).unwrap_err());
+	assert!(matches!(err, Error::LimitExceeded { name: "allocation size", .. }));
+	assert!(from_buffer_with_limits(&buffer, &Limits { max_alloc_bytes: 3 << 20, ..limits }).is_ok());
+}
+
+#[test]
+fn regions_match_the_whole_decode() {
+	let size = SwizzleMap::new(Psm::Psmt4, 32, 32).stored_len() / 2;
+	let image: Vec::<u8> = (0..size).map(|v| (v * 37 % 251) as u8).collect();
+	let buffer = single(Picture::new(4, 32, 32, &image).ramp(0x83, 16));
+
+	for frame_swizzled in [false, true] {
+		let mut image = from_buffer(&buffer).unwrap();
+		image.set_swizzled(frame_swizzled).unwrap();
+
+		let frame = image.get_frame(0);
+		for swizzle in [SwizzleMode::Auto, SwizzleMode::ForceOn, SwizzleMode::ForceOff] {
+			for nibble_order in [NibbleOrder::LowFirst, NibbleOrder::HighFirst] {
+				let options = DecodeOptions::new().swizzle(swizzle).nibble_order(nibble_order);
+				let pixels = frame.decode(&options).unwrap();
+				let cell = frame.decode_region(5, 3, 20, 17, &options).unwrap();
+
+				for (i, pixel) in cell.iter().enumerate() {
+					assert_eq!(*pixel, pixels[(3 + i / 20) * 32 + 5 + i % 20], "{} {:?} {:?}", frame_swizzled, swizzle, nibble_order);
+				}
+			}
+		}
+	}
+}
+
+#[test]
+fn reader_can_skip_a_picture_over_the_limits() {
+	let buffer = file(Alignment::Align16, &[Picture::new(3, 8, 1, &[1; 32]), Picture::new(3, 2, 1, &[2; 8])]);
+	let limits = Limits { max_width: 4, ..Limits::default() };
+	let mut reader = Reader::with_limits(std::io::Cursor::new(&buffer), &limits).unwrap();
+
+	let err = cause(reader.read_frame().unwrap_err());
+	assert!(matches!(err, Error::LimitExceeded { name: "width", .. }));
+	assert!(reader.read_frame().is_err());
+
+	assert_eq!(reader.skip_frame().unwrap().unwrap().width(), 8);
+	assert_eq!(reader.read_frame().unwrap().unwrap().get_pixels().unwrap(), vec![Pixel::from(2, 2, 2, 2); 2]);
+	assert!(reader.read_frame().unwrap().is_none());
+}
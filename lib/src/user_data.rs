@@ -0,0 +1,94 @@
+use crate::error::Error;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use std::borrow::Cow;
+use std::io::Write;
+
+const EXT_IDENT: &[u8; 4] = b"eXt\0";
+const EXT_HEADER_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtHeader {
+	pub user_space_size: u32,
+	pub user_data_size: u32,
+	pub reserved: u32,
+}
+
+/// The user area that follows a picture header. When it starts with the
+/// standard `eXt` header, `raw` holds the user data it describes, followed by
+/// the comment's bytes up to its NUL and everything from the NUL on.
+/// Otherwise `raw` holds the whole area untouched. Either way the area is
+/// written back byte for byte.
+///
+/// # Examples
+///
+/// ```
+/// let image = tim2::load("../assets/test.tm2").unwrap();
+///
+/// if let Some(comment) = image.get_frame(0).info().user_data().comment() {
+///     println!("{}", comment);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserData {
+	pub extended: Option<ExtHeader>,
+	pub raw: Vec::<u8>,
+	pub comment_bytes: Vec::<u8>,
+	pub trailing: Vec::<u8>,
+}
+
+impl UserData {
+	pub fn read(buffer: &[u8]) -> UserData {
+		let opaque = UserData { raw: buffer.to_vec(), ..UserData::default() };
+
+		if buffer.len() < EXT_HEADER_SIZE || &buffer[0..4] != EXT_IDENT {
+			return opaque;
+		}
+
+		let extended = ExtHeader {
+			user_space_size: LittleEndian::read_u32(&buffer[4..8]),
+			user_data_size: LittleEndian::read_u32(&buffer[8..12]),
+			reserved: LittleEndian::read_u32(&buffer[12..16]),
+		};
+
+		let body = &buffer[EXT_HEADER_SIZE..];
+		let user_data_size = extended.user_data_size as usize;
+		if user_data_size > body.len() {
+			return opaque;
+		}
+
+		let (raw, rest) = body.split_at(user_data_size);
+		let end = rest.iter().position(|v| *v == 0).unwrap_or(rest.len());
+
+		UserData {
+			extended: Some(extended),
+			raw: raw.to_vec(),
+			comment_bytes: rest[..end].to_vec(),
+			trailing: rest[end..].to_vec(),
+		}
+	}
+
+	pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+		if let Some(extended) = &self.extended {
+			writer.write_all(EXT_IDENT)?;
+			writer.write_u32::<LittleEndian>(extended.user_space_size)?;
+			writer.write_u32::<LittleEndian>(extended.user_data_size)?;
+			writer.write_u32::<LittleEndian>(extended.reserved)?;
+		}
+
+		writer.write_all(&self.raw)?;
+		writer.write_all(&self.comment_bytes)?;
+		writer.write_all(&self.trailing)?;
+
+		Ok(())
+	}
+
+	/// Returns the comment, decoded lossily if it isn't UTF-8.
+	pub fn comment(&self) -> Option<Cow<'_, str>> {
+		if self.comment_bytes.is_empty() {
+			None
+		} else {
+			Some(String::from_utf8_lossy(&self.comment_bytes))
+		}
+	}
+}
@@ -0,0 +1,221 @@
+use crate::common::*;
+use crate::frame::FrameInfo;
+use crate::image::{Header, HEADER_SIZE};
+
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Severity::Info => write!(f, "info"),
+			Severity::Warning => write!(f, "warning"),
+			Severity::Error => write!(f, "error"),
+		}
+	}
+}
+
+/// A structural problem found by [`validate`], at a byte offset into the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub picture: Option<usize>,
+	pub offset: usize,
+	pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.picture {
+			Some(index) => write!(f, "{}: picture {} @{:#x}: {}", self.severity, index, self.offset, self.message),
+			None => write!(f, "{} @{:#x}: {}", self.severity, self.offset, self.message),
+		}
+	}
+}
+
+struct Diagnostics {
+	list: Vec::<Diagnostic>,
+	picture: Option<usize>,
+	offset: usize,
+}
+
+impl Diagnostics {
+	fn push(&mut self, severity: Severity, message: String) {
+		self.list.push(Diagnostic { severity, picture: self.picture, offset: self.offset, message });
+	}
+}
+
+fn ceil_log2(value: usize) -> usize {
+	value.max(1).next_power_of_two().trailing_zeros() as usize
+}
+
+fn check_picture(info: &FrameInfo, diagnostics: &mut Diagnostics) {
+	let header_size = info.header_size() as usize;
+	let image_size = info.image_size() as usize;
+	let palette_size = info.palette_size() as usize;
+	let total_size = info.total_size() as usize;
+
+	if total_size != header_size + image_size + palette_size {
+		diagnostics.push(Severity::Error, format!(
+			"total size {} does not match header size {} + image size {} + palette size {}",
+			total_size, header_size, image_size, palette_size,
+		));
+	}
+
+	let bpp = info.bpp() as usize;
+	let levels: Vec::<usize> = (0..info.level_count())
+		.map(|v| {
+			let (width, height) = info.level_dimensions(v);
+
+			(width * height * bpp).div_ceil(8)
+		})
+		.collect();
+
+	/* level sizes that don't add up to the image size already fail to parse */
+	if let Some(mipmaps) = info.mipmap_header() {
+		for (level, (size, expected)) in mipmaps.sizes().iter().zip(&levels).enumerate() {
+			let size = *size as usize;
+
+			if size < *expected {
+				diagnostics.push(Severity::Error, format!("mipmap level {} has {} bytes, {} needed", level, size, expected));
+			} else if size > *expected {
+				diagnostics.push(Severity::Warning, format!("mipmap level {} has {} bytes, {} expected", level, size, expected));
+			}
+		}
+	} else if image_size < levels[0] {
+		diagnostics.push(Severity::Error, format!(
+			"image size {} is too small for {}x{} at {} bpp ({} bytes)",
+			image_size, info.width(), info.height(), bpp, levels[0],
+		));
+	} else if image_size > levels[0] {
+		diagnostics.push(Severity::Warning, format!(
+			"image size {} is larger than {}x{} at {} bpp needs ({} bytes)",
+			image_size, info.width(), info.height(), bpp, levels[0],
+		));
+	}
+
+	let clut_size = info.color_entry_count() as usize * info.color_size();
+	if palette_size > 0 && clut_size == 0 {
+		diagnostics.push(Severity::Error, format!("palette size {} with no CLUT entries", palette_size));
+	} else if !palette_size.is_multiple_of(clut_size.max(1)) {
+		diagnostics.push(Severity::Error, format!(
+			"palette size {} is not a multiple of the CLUT size {} ({} entries)",
+			palette_size, clut_size, info.color_entry_count(),
+		));
+	}
+
+	if bpp <= 8 && palette_size == 0 {
+		diagnostics.push(Severity::Error, format!("{} bpp picture has no palette", bpp));
+	}
+
+	let tex0 = info.tex0();
+	if info.gs_tex_0() == 0 {
+		diagnostics.push(Severity::Info, "TEX0 is not set".to_owned());
+		return;
+	}
+
+	let (tw, th) = (ceil_log2(info.width()), ceil_log2(info.height()));
+	if tex0.tw as usize != tw || tex0.th as usize != th {
+		diagnostics.push(Severity::Warning, format!(
+			"TEX0 TW/TH {}/{} do not match {}x{}, expected {}/{}",
+			tex0.tw, tex0.th, info.width(), info.height(), tw, th,
+		));
+	}
+
+	if tex0.psm().map(|v| v.bpp() as usize) != Some(bpp) {
+		diagnostics.push(Severity::Warning, format!("TEX0 PSM {:#04x} does not match {} bpp", tex0.psm, bpp));
+	}
+}
+
+/// Cross-checks the structure of a TIM2 file: picture sizes against their
+/// dimensions and each other, GS registers against the header, and the
+/// picture count and trailing bytes against the data. Files that load fine
+/// can still produce diagnostics.
+///
+/// # Examples
+///
+/// ```
+/// use tim2::Severity;
+///
+/// fn main() {
+///     let buffer = std::fs::read("../assets/test.tm2").unwrap();
+///
+///     for diagnostic in tim2::validate(&buffer) {
+///         println!("{}", diagnostic);
+///     }
+///
+///     /* the sample's total size leaves out its header */
+///     let errors: Vec<_> = tim2::validate(&buffer).into_iter()
+///         .filter(|v| v.severity == Severity::Error)
+///         .collect();
+///
+///     assert_eq!(errors.len(), 1);
+///     assert_eq!(errors[0].picture, Some(0));
+/// }
+/// ```
+pub fn validate(buffer: &[u8]) -> Vec::<Diagnostic> {
+	let mut diagnostics = Diagnostics { list: Vec::new(), picture: None, offset: 0 };
+	let mut offset = 0usize;
+
+	let header = match Header::read(buffer, &mut offset) {
+		Ok(v) => v,
+		Err(err) => {
+			diagnostics.push(Severity::Error, err.to_string());
+			return diagnostics.list;
+		},
+	};
+
+	let alignment = header.alignment.size();
+	offset = align(HEADER_SIZE, alignment);
+
+	for index in 0..header.count {
+		diagnostics.picture = Some(index);
+		diagnostics.offset = offset;
+
+		if offset >= buffer.len() {
+			diagnostics.picture = None;
+			diagnostics.push(Severity::Error, format!("header lists {} pictures, but the data ends after {}", header.count, index));
+			return diagnostics.list;
+		}
+
+		let mut end = offset;
+		let info = match FrameInfo::read(buffer, &mut end) {
+			Ok(v) => v,
+			Err(err) => {
+				diagnostics.push(Severity::Error, err.to_string());
+				return diagnostics.list;
+			},
+		};
+
+		check_picture(&info, &mut diagnostics);
+
+		end = offset + info.header_size() as usize + info.data_size();
+		if end > buffer.len() {
+			diagnostics.push(Severity::Error, format!("picture needs {} bytes, but only {} remain", end - offset, buffer.len() - offset));
+			return diagnostics.list;
+		}
+
+		offset = align(end, alignment);
+	}
+
+	diagnostics.picture = None;
+	diagnostics.offset = offset;
+
+	if offset < buffer.len() {
+		let trailing = buffer.len() - offset;
+
+		if FrameInfo::read(buffer, &mut offset.clone()).is_ok() {
+			diagnostics.push(Severity::Warning, format!("{} trailing bytes look like another picture not counted in the header", trailing));
+		} else {
+			diagnostics.push(Severity::Warning, format!("{} trailing bytes after the last picture", trailing));
+		}
+	}
+
+	diagnostics.list
+}
@@ -165,23 +165,25 @@ impl Viewer {
     fn load_image(&mut self) {
         let path = &self.paths[self.path_idx];
 
-        let load_result = std::panic::catch_unwind(|| tim2::load(path).unwrap());
+        let load_result = match tim2::load(path) {
+            Ok(tim2) => match tim2.frames().first() {
+                Some(frame) => frame
+                    .to_raw(None)
+                    .map(|pixels| (frame.width(), frame.height(), pixels))
+                    .map_err(|err| err.to_string()),
+                None => Err("no pictures found".to_owned()),
+            },
+            Err(err) => Err(err.to_string()),
+        };
 
         match load_result {
-            Ok(tim2) => {
-                let frame = tim2.get_frame(0);
-                let pixels = frame.to_raw(None);
-
-                self.handle = Some(image::Handle::from_pixels(
-                    frame.width() as _,
-                    frame.height() as _,
-                    pixels,
-                ));
+            Ok((width, height, pixels)) => {
+                self.handle = Some(image::Handle::from_pixels(width as _, height as _, pixels));
 
                 self.state = State::Loaded;
             }
-            Err(_) => {
-                self.error_msg = "Failed to load image ".to_owned();
+            Err(err) => {
+                self.error_msg = format!("Failed to load image: {}", err);
 
                 self.state = State::Error;
             }